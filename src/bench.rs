@@ -0,0 +1,72 @@
+use super::*;
+
+/// Simulation timings measured by [Tas::benchmark_run].
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    /// The number of simulated frames.
+    pub frames: usize,
+    /// The total time spent simulating, in seconds.
+    pub total_time: f64,
+    /// The fastest frame, in seconds.
+    pub min_frame_time: f64,
+    /// The slowest frame, in seconds.
+    pub max_frame_time: f64,
+}
+
+impl BenchReport {
+    /// The average time per frame, in seconds.
+    pub fn avg_frame_time(&self) -> f64 {
+        self.total_time / self.frames.max(1) as f64
+    }
+
+    /// The number of frames that can be simulated per second.
+    pub fn fps(&self) -> f64 {
+        self.frames as f64 / self.total_time.max(f64::EPSILON)
+    }
+}
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Loads the run and replays it as fast as possible without drawing,
+    /// measuring how long each frame takes to simulate.
+    /// The run is left at its end afterwards.
+    pub fn benchmark_run(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<BenchReport, Box<dyn std::error::Error>> {
+        self.load_run(path)?;
+
+        let mut report = BenchReport {
+            frames: 0,
+            total_time: 0.0,
+            min_frame_time: f64::INFINITY,
+            max_frame_time: 0.0,
+        };
+        while self.replay.is_some() {
+            let frame = self.frame;
+            let start = std::time::Instant::now();
+            self.next_frame();
+            let time = start.elapsed().as_secs_f64();
+            if self.frame == frame {
+                // Replay has ended
+                break;
+            }
+            report.frames += 1;
+            report.total_time += time;
+            report.min_frame_time = report.min_frame_time.min(time);
+            report.max_frame_time = report.max_frame_time.max(time);
+        }
+        if report.frames == 0 {
+            report.min_frame_time = 0.0;
+        }
+
+        log_info!(
+            "Simulated {} frames at {:.0} fps (min {:.3}ms, avg {:.3}ms, max {:.3}ms)",
+            report.frames,
+            report.fps(),
+            report.min_frame_time * 1000.0,
+            report.avg_frame_time() * 1000.0,
+            report.max_frame_time * 1000.0,
+        );
+        Ok(report)
+    }
+}
@@ -0,0 +1,61 @@
+/// Accumulates the real time to simulate it in whole frames.
+///
+/// The remainder (always less than a frame) carries over to the next update,
+/// so that the simulation keeps up with the real time without drifting.
+/// It belongs to the moment it was accumulated at though, so it is dropped with [Self::reset]
+/// whenever the simulation stops (e.g. on pause): resuming then starts from a whole frame,
+/// instead of simulating a burst of frames right away.
+#[derive(Debug, Default)]
+pub(crate) struct FrameClock {
+    time: f64,
+}
+
+impl FrameClock {
+    /// Adds the time (in seconds) to simulate.
+    pub fn advance(&mut self, delta_time: f64) {
+        self.time += delta_time.max(0.0);
+    }
+
+    /// Takes the time of a frame, if a whole one has accumulated.
+    pub fn take_frame(&mut self, interval: f64) -> bool {
+        if self.time < interval {
+            return false;
+        }
+        self.time -= interval;
+        true
+    }
+
+    /// Drops the accumulated time.
+    pub fn reset(&mut self) {
+        self.time = 0.0;
+    }
+
+    /// The accumulated time (in seconds), less than a frame after taking the whole ones.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_after_pause() {
+        let mut clock = FrameClock::default();
+        clock.advance(2.5);
+        assert!(clock.take_frame(1.0));
+        assert!(clock.take_frame(1.0));
+        assert!(!clock.take_frame(1.0));
+
+        // Paused with half a frame accumulated
+        clock.reset();
+        // Resuming simulates one frame per frame of time, without a burst
+        clock.advance(0.75);
+        assert!(!clock.take_frame(1.0));
+        clock.advance(0.25);
+        assert!(clock.take_frame(1.0));
+        assert!(!clock.take_frame(1.0));
+        assert_eq!(clock.time(), 0.0);
+    }
+}
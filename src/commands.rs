@@ -0,0 +1,141 @@
+use super::*;
+
+/// The number of lines kept in the console log.
+const CONSOLE_LOG_SIZE: usize = 10;
+
+const HELP: &str = "Commands: seek <frame>, step [frames], pause, play, speed <scale>, \
+    save, load <index>, saverun [path], replay [path], loop [<start> <end>], trim <start> <end>, \
+    goal [frame], slot <name>, repro <path> <note>, help";
+
+fn parse_arg<A: std::str::FromStr>(args: &[&str], index: usize, name: &str) -> Result<A, String> {
+    let arg = args
+        .get(index)
+        .ok_or_else(|| format!("Missing argument <{name}>"))?;
+    arg.parse().map_err(|_| format!("Invalid {name}: {arg}"))
+}
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Executes a text command (see `help`) and returns its output.
+    pub fn execute_command(&mut self, command: &str) -> Result<String, String> {
+        let mut words = command.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(String::new());
+        };
+        let args: Vec<&str> = words.collect();
+
+        match name {
+            "help" => Ok(HELP.to_owned()),
+            "seek" => {
+                let frame = parse_arg(&args, 0, "frame")?;
+                self.seek(frame);
+                Ok(format!("Frame {}", self.frame))
+            }
+            "step" => {
+                let frames = if args.is_empty() {
+                    1
+                } else {
+                    parse_arg(&args, 0, "frames")?
+                };
+                self.step(frames);
+                Ok(format!("Frame {}", self.frame))
+            }
+            "pause" => {
+                self.pause();
+                Ok("Paused".to_owned())
+            }
+            "play" => {
+                self.paused = false;
+                Ok("Playing".to_owned())
+            }
+            "speed" => {
+                let scale: f64 = parse_arg(&args, 0, "scale")?;
+                self.set_time_scale(scale);
+                Ok(format!("Time scale {:.2}", self.time_scale))
+            }
+            "save" => {
+                self.save_state();
+                Ok(format!("Saved state #{}", self.saved_states.len() - 1))
+            }
+            "load" => {
+                let index: usize = parse_arg(&args, 0, "index")?;
+                if index >= self.saved_states.len() {
+                    return Err(format!("There is no save state #{index}"));
+                }
+                self.selected_state = index;
+                self.load_state(index);
+                Ok(format!("Loaded state #{index}"))
+            }
+            "saverun" => {
+                let path = args
+                    .first()
+                    .map_or(self.save_file.clone(), |s| s.to_string());
+                self.save_run(&path).map_err(|err| err.to_string())?;
+                Ok(format!("Saved run to {path}"))
+            }
+            "replay" => {
+                let path = args
+                    .first()
+                    .map_or(self.save_file.clone(), |s| s.to_string());
+                self.load_run(&path).map_err(|err| err.to_string())?;
+                Ok(format!("Replaying {path}"))
+            }
+            "loop" => {
+                if args.is_empty() {
+                    self.stop_loop();
+                    return Ok("Stopped looping".to_owned());
+                }
+                let start = parse_arg(&args, 0, "start")?;
+                let end = parse_arg(&args, 1, "end")?;
+                self.loop_range(start, end).map_err(|err| err.to_string())?;
+                Ok(format!("Looping frames {start}..{end}"))
+            }
+            "trim" => {
+                let start = parse_arg(&args, 0, "start")?;
+                let end = parse_arg(&args, 1, "end")?;
+                self.trim_frames(start, end)
+                    .map_err(|err| err.to_string())?;
+                Ok(format!("Removed frames {start}..{end}"))
+            }
+            "goal" => {
+                if args.is_empty() {
+                    self.set_goal_frame(None);
+                    return Ok("Cleared the goal".to_owned());
+                }
+                let frame = parse_arg(&args, 0, "frame")?;
+                self.set_goal_frame(Some(frame));
+                Ok(format!("Goal frame {frame}"))
+            }
+            "slot" => {
+                let slot: String = parse_arg(&args, 0, "name")?;
+                self.switch_recording(&slot);
+                Ok(format!("Recording {:?}", self.recording_slot()))
+            }
+            "repro" => {
+                let path: String = parse_arg(&args, 0, "path")?;
+                let note = args[1..].join(" ");
+                if note.is_empty() {
+                    return Err("Missing argument <note>".to_owned());
+                }
+                self.export_repro(&path, &note)
+                    .map_err(|err| err.to_string())?;
+                Ok(format!("Exported a reproduction to {path}"))
+            }
+            _ => Err(format!("Unknown command: {name}")),
+        }
+    }
+
+    /// Executes the command and writes it with the output into the console log.
+    pub(crate) fn run_console_command(&mut self, command: &str) {
+        let output = match self.execute_command(command) {
+            Ok(output) => output,
+            Err(err) => format!("Error: {err}"),
+        };
+        self.console_log.push_back(format!("> {command}"));
+        if !output.is_empty() {
+            self.console_log.push_back(output);
+        }
+        while self.console_log.len() > CONSOLE_LOG_SIZE {
+            self.console_log.pop_front();
+        }
+    }
+}
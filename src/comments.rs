@@ -0,0 +1,60 @@
+use super::*;
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Attaches a note to the frames of the run, e.g. to explain why a section is done that way.
+    /// Comments are saved with the run.
+    pub fn add_comment(&mut self, frames: std::ops::Range<usize>, text: impl Into<String>) {
+        let index = self
+            .comments
+            .partition_point(|(range, _)| range.start <= frames.start);
+        self.comments.insert(index, (frames, text.into()));
+    }
+
+    /// Removes the comment by its index in [Self::comments].
+    pub fn remove_comment(&mut self, index: usize) {
+        if index < self.comments.len() {
+            self.comments.remove(index);
+        }
+    }
+
+    /// All comments of the run along with the frames they annotate, ordered by the first frame.
+    pub fn comments(&self) -> &[(std::ops::Range<usize>, String)] {
+        &self.comments
+    }
+
+    /// The indices of the comments annotating the current frame.
+    pub(crate) fn active_comments(&self) -> impl Iterator<Item = usize> + '_ {
+        self.comments
+            .iter()
+            .enumerate()
+            .filter(|(_, (range, _))| range.contains(&self.frame))
+            .map(|(index, _)| index)
+    }
+
+    /// Adds a comment typed into the UI as `<start>..<end> <text>`,
+    /// or just `<text>` to annotate the current frame.
+    pub(crate) fn add_comment_from_input(&mut self, input: &str) -> Result<(), String> {
+        let input = input.trim();
+        let (frames, text) = match input.split_once(' ') {
+            Some((range, text)) if range.contains("..") => {
+                let (start, end) = range.split_once("..").unwrap();
+                let parse = |frame: &str| {
+                    frame
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid frame: {frame}"))
+                };
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start >= end {
+                    return Err(format!("Invalid frame range: {start}..{end}"));
+                }
+                (start..end, text.trim())
+            }
+            _ => (self.frame..self.frame + 1, input),
+        };
+        if text.is_empty() {
+            return Err("The comment is empty".to_owned());
+        }
+        self.add_comment(frames, text);
+        Ok(())
+    }
+}
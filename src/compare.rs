@@ -0,0 +1,140 @@
+use super::*;
+
+/// A second run replayed with its own game instance alongside the main one,
+/// see [Tas::compare_runs].
+pub(crate) struct Comparison<T: Tasable> {
+    game: T,
+    /// The frame the game is at.
+    pub frame: usize,
+    history: History<T::Saved>,
+    inputs: Vec<FrameInput<geng::Event>>,
+    pub total_frames: usize,
+    /// The textures the two games are drawn to, reused across frames.
+    textures: Option<(ugli::Texture, ugli::Texture)>,
+}
+
+impl<T: geng::State + Tasable> Comparison<T> {
+    /// Simulates the next frame of the run, if it has not ended yet.
+    fn step(&mut self, delta_time: f64) {
+        if self.frame >= self.total_frames {
+            return;
+        }
+        if let Some(state) = self.history.get_exact(self.frame) {
+            self.game.load(state.clone());
+        }
+        let events = frame_inputs(&self.inputs, self.frame..self.frame + 1);
+        for event in events.into_iter().flatten() {
+            self.game.handle_event(event);
+        }
+        self.game.update(delta_time);
+        self.game.fixed_update(delta_time);
+        self.frame += 1;
+    }
+
+    /// Restores the nearest snapshot and simulates the run up to the frame.
+    fn seek(&mut self, frame: usize, delta_time: f64) {
+        let target = frame.min(self.total_frames);
+        let Some((start, state)) = self.history.nearest(target) else {
+            return;
+        };
+        self.game.load(state.clone());
+        self.frame = start;
+        while self.frame < target {
+            self.step(delta_time);
+        }
+    }
+}
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Replays two runs side by side to compare them, frame by frame:
+    /// the first one as usual, and the second one with the given game instance,
+    /// drawn on the right half of the screen.
+    ///
+    /// The second game only gets the recorded events, so games reading the pressed keys
+    /// from the window see the ones of the first run.
+    pub fn compare_runs(
+        &mut self,
+        mut game: T,
+        first: impl AsRef<std::path::Path>,
+        second: impl AsRef<std::path::Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut saved = read_run::<T>(&std::fs::read(second)?)?;
+        migrate_run(&game, &mut saved);
+        self.resolve_base_state(&mut saved)?;
+        let Some(initial_state) = saved.history.first() else {
+            return Err("The run has no initial state".into());
+        };
+        game.load(initial_state.clone());
+        self.load_run(first)?;
+        self.comparison = Some(Comparison {
+            game,
+            frame: 0,
+            total_frames: total_frames(&saved.inputs),
+            history: saved.history,
+            inputs: saved.inputs,
+            textures: None,
+        });
+        Ok(())
+    }
+
+    /// Stops replaying the second run, see [Self::compare_runs].
+    pub fn stop_comparison(&mut self) {
+        self.comparison = None;
+    }
+
+    /// Brings the compared run to the current frame, simulating it in lockstep with the main one.
+    pub(crate) fn sync_comparison(&mut self) {
+        let Some(comparison) = &mut self.comparison else {
+            return;
+        };
+        if comparison.frame + 1 == self.frame {
+            comparison.step(self.fixed_delta_time);
+        } else if comparison.frame != self.frame.min(comparison.total_frames) {
+            // The main run has been seeked or loaded
+            comparison.seek(self.frame, self.fixed_delta_time);
+        }
+    }
+
+    /// Draws the main game on the left half of the screen and the compared one on the right.
+    pub(crate) fn draw_comparison(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        let geng = self.geng().clone();
+        let Some(comparison) = &mut self.comparison else {
+            return;
+        };
+        let size = framebuffer.size();
+        let half = vec2((size.x / 2).max(1), size.y.max(1));
+        if comparison
+            .textures
+            .as_ref()
+            .map_or(true, |(texture, _)| texture.size() != half)
+        {
+            comparison.textures = Some((
+                ugli::Texture::new_uninitialized(geng.ugli(), half),
+                ugli::Texture::new_uninitialized(geng.ugli(), half),
+            ));
+        }
+        let (left, right) = comparison.textures.as_mut().unwrap();
+
+        for (texture, game) in [
+            (&mut *left, &mut self.game),
+            (&mut *right, &mut comparison.game),
+        ] {
+            let mut framebuffer =
+                ugli::Framebuffer::new_color(geng.ugli(), ugli::ColorAttachment::Texture(texture));
+            ugli::clear(&mut framebuffer, Some(Rgba::BLACK), None, None);
+            game.draw(&mut framebuffer);
+        }
+
+        let half = half.map(|x| x as f32);
+        for (offset, texture) in [(0.0, &*left), (half.x, &*right)] {
+            geng.draw2d().draw2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw2d::TexturedQuad::new(
+                    Aabb2::from_corners(vec2(offset, 0.0), vec2(offset + half.x, half.y)),
+                    texture,
+                ),
+            );
+        }
+    }
+}
@@ -0,0 +1,470 @@
+use super::*;
+
+/// Configuration of the TAS behaviour.
+#[derive(Debug, Clone)]
+pub struct TasConfig {
+    /// How the TAS menu is opened with [Keybinds::menu].
+    pub menu_activation: MenuActivation,
+    pub keybinds: Keybinds,
+    /// The number of frames between state snapshots kept in the history.
+    /// Seeking simulates at most that many frames from the nearest snapshot,
+    /// so larger values trade seeking speed for memory.
+    /// Takes effect for new recordings.
+    pub snapshot_interval: usize,
+    /// The time scale when the TAS starts.
+    pub default_time_scale: f64,
+    /// How much [Keybinds::slower] and [Keybinds::faster] change the time scale by.
+    pub time_scale_step: f64,
+    /// The largest time scale that can be set, so that a huge one does not
+    /// freeze the game by simulating too many frames on every update.
+    pub max_time_scale: f64,
+    /// The time scale that [Keybinds::slow_motion] switches to at first.
+    pub slow_time_scale: f64,
+    /// Whether the simulation is paused when the TAS starts.
+    pub start_paused: bool,
+    /// Whether to capture the initial state of the recording on its first frame
+    /// instead of when the TAS is created, for games that are not fully initialized by then.
+    pub defer_initial_state: bool,
+    /// Whether loading a run pauses the simulation at its first frame,
+    /// so the replay only starts once unpaused. Also applies to the runs in a playlist.
+    pub pause_on_load: bool,
+    /// Whether pausing discards the events queued for the next frame.
+    /// By default they are kept and recorded on the first frame after resuming.
+    pub clear_queued_on_pause: bool,
+    /// The number of frames simulated per second (of scaled real time), independent of
+    /// the rate of geng's fixed updates, so that runs have the same frame count on builds
+    /// configured differently. `None` (the default) simulates a frame per fixed update.
+    pub frame_rate: Option<f64>,
+    /// Whether recording advances exactly one frame per fixed update,
+    /// ignoring the time scale and real time variance.
+    pub lockstep_recording: bool,
+    /// Whether recording only captures the inputs without simulating the game,
+    /// e.g. to author an input track against a static scene.
+    /// The recorded inputs are the same as with normal recording.
+    pub record_only: bool,
+    /// A fixed framebuffer size given to the game with [crate::Tasable::restore_framebuffer_size]
+    /// and recorded in the states and runs, regardless of the actual window size.
+    /// Makes runs reproducible for games that derive their simulation from the size.
+    pub lock_framebuffer_size: Option<vec2<usize>>,
+    /// Whether to keep calling the game's `update` with the real delta time while paused,
+    /// so that ambient animations keep playing. `fixed_update` stays frozen.
+    /// Whatever `update` changes in the meantime is not recorded,
+    /// so it should only affect the visuals.
+    pub animate_while_paused: bool,
+    /// Whether to draw interpolated frames (see [Tasable::interpolate]) while running.
+    pub interpolation: bool,
+    /// Whether to draw interpolated frames only while replaying,
+    /// for smooth playback on displays faster than the simulation.
+    pub replay_interpolation: bool,
+    /// Whether keys and buttons pressed and released within the same frame are ignored.
+    /// Such taps bloat the run and may be handled differently depending on the event order,
+    /// but some games rely on them.
+    pub remove_taps: bool,
+    /// Whether the command console can be opened with [Keybinds::console].
+    pub enable_console: bool,
+    /// Whether to refuse loading runs recorded with a different fixed delta time
+    /// instead of only warning about it.
+    pub refuse_delta_time_mismatch: bool,
+    /// A file the recorded frames are appended to as they are recorded.
+    /// If the file is left from a previous session (e.g. after a crash),
+    /// the recording is restored from it on startup.
+    pub recording_log: Option<String>,
+    /// Whether to simulate a frame on every `update` (instead of on every fixed update)
+    /// and record the real delta time to pass to the game's `update` when replaying.
+    /// Makes the TAS usable for games with a variable time step.
+    /// Replays still simulate one frame per `update`, so their speed depends on the frame rate.
+    pub record_delta_time: bool,
+    /// Whether to record the real time between the recorded frames (including the time spent
+    /// paused or in slow motion) to play the run back the way it was performed,
+    /// see [Self::replay_pacing]. The simulation itself is not affected.
+    pub record_pacing: bool,
+    /// Whether replays schedule the frames at the intervals recorded with [Self::record_pacing]
+    /// (scaled by the time scale) instead of at a uniform rate.
+    /// Has no effect with [Self::record_delta_time].
+    pub replay_pacing: bool,
+    /// Whether to record the repeated `KeyDown` events of held keys that some platforms generate.
+    /// By default they are dropped, so that each press is recorded once.
+    pub record_key_repeats: bool,
+    /// Whether to record the cursor position on every frame and restore it when replaying,
+    /// for games that poll the cursor position directly.
+    pub record_cursor: bool,
+    /// Whether to record the time within the frame at which each event arrived.
+    /// Makes the run bigger, but preserves the timing for games sensitive to it.
+    pub record_timestamps: bool,
+    /// The only keys to record, if set. Other keys are given to the game as they come,
+    /// but are not recorded and do not affect the recorded pressed state.
+    pub recorded_keys: Option<HashSet<geng::Key>>,
+    /// The only mouse buttons to record, if set, the same way as [Self::recorded_keys].
+    pub recorded_buttons: Option<HashSet<geng::MouseButton>>,
+    /// Whether runs, save states, and macros are written as compact JSON,
+    /// which takes about half the space, instead of the indented (default) one.
+    pub compact_json: bool,
+    /// Whether runs starting from the game state of a save state reference it by its hash
+    /// instead of storing the whole state, which makes the files smaller and ties segments
+    /// to a shared checkpoint. Such runs can only be loaded while the state is among the save states.
+    pub relative_runs: bool,
+    /// The minimum time (in seconds) between writes of the save states to disk.
+    /// Changes made in between are written together, and any pending ones are written on exit.
+    pub savestates_flush_interval: f64,
+    /// A directory to store every save state in its own file (`state_0.json`, `state_1.json`...)
+    /// instead of all of them in `savedstates.json`, so that they can be managed separately
+    /// and a broken file only loses one state.
+    pub savestates_dir: Option<String>,
+    /// Whether to merge consecutive save states with identical game states when loading them.
+    pub dedup_savestates: bool,
+    /// Whether to write each state in the save state file (including the history snapshots)
+    /// as the changes to the one before it, which is much smaller if they differ slightly,
+    /// but takes longer to save and load. Files written either way can be loaded.
+    pub delta_savestates: bool,
+    /// Whether to briefly show on-screen notifications for actions like saving a state.
+    pub show_notifications: bool,
+    /// A template for the window title to show the TAS status in, e.g. for screen recorders.
+    /// `{status}` is replaced with "Recording" or "Replay", `{frame}` with the current frame,
+    /// `{total}` with the length of the run, and `{percent}` with the progress through it.
+    pub window_title: Option<String>,
+    /// Whether to catch the panics in the game's update so that they pause the simulation
+    /// instead of crashing. The game may be left in an inconsistent state after a panic.
+    pub catch_panics: bool,
+    /// Whether to load the most recently modified run on startup,
+    /// unless a recording has been recovered from the log.
+    pub load_recent_run: bool,
+    /// Whether to compare the replayed game state with the recorded snapshots.
+    pub detect_desync: bool,
+    /// What happens when the replay desyncs.
+    pub desync_policy: DesyncPolicy,
+    /// Whether to save a snapshot of the current state
+    /// before loading a save state or a run.
+    pub snapshot_before_load: bool,
+    /// Whether the save states are kept when a different run is loaded.
+    /// They can still be loaded, but belong to the previous run (or recording),
+    /// so loading one replaces the loaded run.
+    /// Otherwise they are removed, from the save file as well,
+    /// and only the automatic snapshot of the previous recording is kept (if enabled).
+    pub keep_states_on_load: bool,
+    /// Whether loading a state saved during a replay continues the replay from it.
+    /// Otherwise the replayed inputs up to the state are kept and recording continues.
+    pub resume_replay_from_states: bool,
+    /// Maximum number of automatic snapshots kept in the save state list.
+    /// The oldest ones are removed first.
+    pub max_auto_snapshots: usize,
+    /// Whether to save a state every time a breakpoint is hit.
+    pub snapshot_on_breakpoint: bool,
+    /// A directory to save the run into once the game reports it as finished
+    /// (see [crate::Tasable::is_finished]), as `finished_{frame}_{timestamp}.json`.
+    pub finished_runs_dir: Option<String>,
+    /// Whether to set the pressed keys and buttons of the window to the simulated ones,
+    /// for games that poll them. Purely event-driven games can disable it
+    /// to leave the real input state of the window alone.
+    pub sync_pressed_state: bool,
+    /// Every how many frames to hash the drawn frame, storing the hashes in the run
+    /// while recording and comparing them while replaying, to detect rendering
+    /// that is not deterministic even though the simulation is.
+    /// Reading the frames back is slow, so it is disabled by default.
+    /// The window has to be the same size for the hashes to match.
+    pub render_hash_interval: Option<usize>,
+    /// Whether the overlay is only shown while [Keybinds::peek_ui] is held (or the menu is open),
+    /// so the game is drawn unobstructed the rest of the time.
+    pub peek_ui: bool,
+    /// How many frames before and after the current one to list the held inputs of
+    /// in the overlay. 0 (the default) hides the list.
+    /// It can be toggled with [Keybinds::input_overlay], and stays visible
+    /// while the rest of the overlay is hidden.
+    pub input_strip_frames: usize,
+    /// Whether to show a preview of the frame hovered on the timeline.
+    /// The game is drawn from the nearest snapshot, so it is exact with
+    /// [Self::snapshot_interval] of 1. The game state is loaded temporarily to draw it.
+    pub scrub_preview: bool,
+    /// Whether to never show the built-in overlay, for hosts that build their own UI
+    /// on top of the public methods. Only read when the TAS is created.
+    pub disable_ui: bool,
+}
+
+impl Default for TasConfig {
+    fn default() -> Self {
+        Self {
+            menu_activation: MenuActivation::Hold,
+            keybinds: default(),
+            snapshot_interval: 1,
+            default_time_scale: 1.0,
+            time_scale_step: 0.05,
+            max_time_scale: 10.0,
+            slow_time_scale: 0.25,
+            start_paused: true,
+            defer_initial_state: false,
+            pause_on_load: false,
+            clear_queued_on_pause: false,
+            frame_rate: None,
+            lockstep_recording: false,
+            record_only: false,
+            lock_framebuffer_size: None,
+            animate_while_paused: false,
+            interpolation: false,
+            replay_interpolation: false,
+            remove_taps: false,
+            enable_console: false,
+            refuse_delta_time_mismatch: false,
+            recording_log: None,
+            record_delta_time: false,
+            record_pacing: false,
+            replay_pacing: false,
+            record_key_repeats: false,
+            record_cursor: false,
+            record_timestamps: false,
+            recorded_keys: None,
+            recorded_buttons: None,
+            compact_json: false,
+            relative_runs: false,
+            savestates_flush_interval: 0.5,
+            savestates_dir: None,
+            dedup_savestates: false,
+            delta_savestates: false,
+            show_notifications: true,
+            window_title: None,
+            catch_panics: false,
+            load_recent_run: false,
+            detect_desync: false,
+            desync_policy: DesyncPolicy::Pause,
+            snapshot_before_load: true,
+            keep_states_on_load: true,
+            resume_replay_from_states: true,
+            max_auto_snapshots: 5,
+            snapshot_on_breakpoint: false,
+            finished_runs_dir: None,
+            sync_pressed_state: true,
+            render_hash_interval: None,
+            peek_ui: false,
+            input_strip_frames: 0,
+            scrub_preview: false,
+            disable_ui: false,
+        }
+    }
+}
+
+impl TasConfig {
+    /// Whether the event is part of the run, see [Self::recorded_keys].
+    /// Only the events that can be saved are recorded, see [StableEvent].
+    pub(crate) fn records(&self, event: &geng::Event) -> bool {
+        match event {
+            geng::Event::KeyDown { key } | geng::Event::KeyUp { key } => self
+                .recorded_keys
+                .as_ref()
+                .map_or(true, |keys| keys.contains(key)),
+            geng::Event::MouseDown { button, .. } | geng::Event::MouseUp { button, .. } => self
+                .recorded_buttons
+                .as_ref()
+                .map_or(true, |buttons| buttons.contains(button)),
+            event => StableEvent::from_event(event).is_some(),
+        }
+    }
+}
+
+/// Controls what happens when a replayed state differs from the recorded one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DesyncPolicy {
+    /// Pause the replay at the desynced state, resuming continues from the recorded state.
+    Pause,
+    /// Keep simulating from the desynced state.
+    Continue,
+    /// Load the recorded state and keep replaying.
+    ReloadCheckpoint,
+    /// Stop the replay.
+    Abort,
+    /// Replay again from the initial state, in case the desync was caused by some state
+    /// left over from before the replay. Pauses if it still desyncs after `max_retries` retries.
+    RetryFromStart { max_retries: usize },
+}
+
+/// Controls how the menu key opens the TAS menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuActivation {
+    /// The menu is open while the key is held.
+    Hold,
+    /// The menu is opened and closed by pressing the key.
+    Toggle,
+}
+
+/// Keys used to control the TAS.
+/// All keys except for `menu` are only active while the menu is open.
+#[derive(Debug, Clone)]
+pub struct Keybinds {
+    /// Opens the TAS menu, capturing the input and pausing the simulation.
+    pub menu: geng::Key,
+    pub save_run: geng::Key,
+    /// Saves a copy of the run next to the save file, see [crate::Tas::backup_run].
+    pub backup_run: geng::Key,
+    pub toggle_replay: geng::Key,
+    pub save_state: geng::Key,
+    /// Loads the selected save state.
+    pub load_state: geng::Key,
+    pub pause: geng::Key,
+    /// Stops the replay at the current frame and continues recording from there.
+    pub take_over: geng::Key,
+    /// Starts a new recording from the current frame of the replay, see [crate::Tas::fork_replay].
+    pub fork: geng::Key,
+    /// Simulates a single frame.
+    /// Keys not bound to any action can be held while the menu is open to record them.
+    pub step: geng::Key,
+    /// Decreases the time scale.
+    pub slower: geng::Key,
+    /// Increases the time scale.
+    pub faster: geng::Key,
+    /// Switches between the normal speed and the last used slow-motion time scale.
+    pub slow_motion: geng::Key,
+    /// Selects and loads the next save state.
+    pub next_state: geng::Key,
+    /// Selects and loads the previous save state.
+    pub prev_state: geng::Key,
+    /// Loads the next save state, wrapping around at the end.
+    /// Goes backwards while Shift is held.
+    pub cycle_state: geng::Key,
+    /// Seeks to the frame of the next save state, e.g. to skip through a replay.
+    pub next_state_frame: geng::Key,
+    /// Seeks to the frame of the previous save state.
+    pub prev_state_frame: geng::Key,
+    /// Plays the replay backwards or forwards again, see [crate::Tas::set_reverse].
+    pub reverse: geng::Key,
+    /// Restarts the recording from its initial state.
+    pub restart: geng::Key,
+    /// Starts or stops recording a macro.
+    pub record_macro: geng::Key,
+    /// Plays the last recorded or played macro.
+    pub play_macro: geng::Key,
+    /// Toggles the command console, if it is enabled.
+    pub console: geng::Key,
+    /// Toggles the list of keybinds.
+    pub help: geng::Key,
+    /// Toggles the recent log messages of the TAS, e.g. failures to save or load.
+    pub log_view: geng::Key,
+    /// Restores the most recently deleted save state.
+    pub undo_delete: geng::Key,
+    /// Shows the overlay while held, if [TasConfig::peek_ui] is enabled.
+    /// Works without opening the menu.
+    pub peek_ui: geng::Key,
+    /// Shows or hides the held inputs (see [TasConfig::input_strip_frames])
+    /// independently of the rest of the overlay. Works without opening the menu.
+    pub input_overlay: geng::Key,
+}
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        Self {
+            menu: geng::Key::LAlt,
+            save_run: geng::Key::S,
+            backup_run: geng::Key::B,
+            toggle_replay: geng::Key::R,
+            save_state: geng::Key::K,
+            load_state: geng::Key::L,
+            pause: geng::Key::P,
+            step: geng::Key::Period,
+            take_over: geng::Key::O,
+            fork: geng::Key::F,
+            slower: geng::Key::Left,
+            faster: geng::Key::Right,
+            slow_motion: geng::Key::Z,
+            next_state: geng::Key::Up,
+            prev_state: geng::Key::Down,
+            cycle_state: geng::Key::Tab,
+            next_state_frame: geng::Key::PageDown,
+            prev_state_frame: geng::Key::PageUp,
+            reverse: geng::Key::Backspace,
+            restart: geng::Key::T,
+            record_macro: geng::Key::M,
+            play_macro: geng::Key::N,
+            console: geng::Key::F2,
+            help: geng::Key::F1,
+            log_view: geng::Key::F4,
+            undo_delete: geng::Key::U,
+            peek_ui: geng::Key::F3,
+            input_overlay: geng::Key::F5,
+        }
+    }
+}
+
+impl Keybinds {
+    /// Lists all the keybinds (except for `menu`) with descriptions of their actions.
+    pub fn list(&self) -> Vec<(geng::Key, &'static str)> {
+        vec![
+            (self.save_run, "Save run"),
+            (self.backup_run, "Save a backup copy of the run"),
+            (self.toggle_replay, "Toggle replay"),
+            (self.save_state, "Save state"),
+            (self.load_state, "Load selected state"),
+            (self.pause, "Pause"),
+            (self.step, "Advance one frame"),
+            (self.take_over, "Take over the replay"),
+            (self.fork, "Fork a new run from the replay"),
+            (self.slower, "Decrease time scale"),
+            (self.faster, "Increase time scale"),
+            (self.slow_motion, "Toggle slow motion"),
+            (self.next_state, "Load next state"),
+            (self.prev_state, "Load previous state"),
+            (self.cycle_state, "Cycle through states (Shift to go back)"),
+            (self.next_state_frame, "Seek to the next save state"),
+            (self.prev_state_frame, "Seek to the previous save state"),
+            (self.reverse, "Toggle reverse replay"),
+            (self.restart, "Restart recording"),
+            (self.record_macro, "Record macro"),
+            (self.play_macro, "Play macro"),
+            (self.console, "Toggle console"),
+            (self.help, "Toggle help"),
+            (self.log_view, "Toggle log"),
+            (self.undo_delete, "Undo save state deletion"),
+            (self.peek_ui, "Hold to show the overlay (in peek mode)"),
+            (self.input_overlay, "Toggle the held inputs"),
+        ]
+    }
+
+    /// Whether the key is recorded while the menu is open, so that it can be held
+    /// while advancing frame by frame: it is not bound to anything and is not a modifier,
+    /// which are used along with the keybinds.
+    pub fn records_in_menu(&self, key: geng::Key) -> bool {
+        const MODIFIERS: [geng::Key; 6] = [
+            geng::Key::LShift,
+            geng::Key::RShift,
+            geng::Key::LCtrl,
+            geng::Key::RCtrl,
+            geng::Key::LAlt,
+            geng::Key::RAlt,
+        ];
+        key != self.menu
+            && !MODIFIERS.contains(&key)
+            && !self.list().iter().any(|&(bound, _)| bound == key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_recorded_in_menu() {
+        let mut keys = Keybinds {
+            menu: geng::Key::Tab,
+            ..default()
+        };
+        keys.cycle_state = geng::Key::Q;
+        assert!(keys.records_in_menu(geng::Key::D));
+        assert!(!keys.records_in_menu(geng::Key::Tab));
+        assert!(!keys.records_in_menu(geng::Key::Q));
+        assert!(!keys.records_in_menu(keys.step));
+        assert!(!keys.records_in_menu(geng::Key::LShift));
+        assert!(!keys.records_in_menu(geng::Key::RShift));
+    }
+
+    #[test]
+    fn only_whitelisted_keys_recorded() {
+        let config = TasConfig {
+            recorded_keys: Some([geng::Key::D].into_iter().collect()),
+            ..default()
+        };
+        let press = |key| geng::Event::KeyDown { key };
+        assert!(config.records(&press(geng::Key::D)));
+        assert!(!config.records(&press(geng::Key::A)));
+        assert!(!config.records(&geng::Event::KeyUp { key: geng::Key::A }));
+        // Buttons are not limited by the keys
+        assert!(config.records(&geng::Event::MouseDown {
+            position: vec2(0.0, 0.0),
+            button: geng::MouseButton::Left,
+        }));
+    }
+}
@@ -0,0 +1,63 @@
+use super::*;
+
+/// The maximum number of differences reported by [Tas::diff_states].
+const MAX_DIFFERENCES: usize = 100;
+
+/// Collects the paths of the values that differ between `a` and `b`.
+fn json_diff(a: &serde_json::Value, b: &serde_json::Value, path: &str, diff: &mut Vec<String>) {
+    use serde_json::Value;
+
+    if diff.len() >= MAX_DIFFERENCES {
+        return;
+    }
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, value) in a {
+                let path = format!("{path}.{key}");
+                match b.get(key) {
+                    Some(other) => json_diff(value, other, &path, diff),
+                    None => diff.push(format!("{path}: removed")),
+                }
+            }
+            for key in b.keys().filter(|key| !a.contains_key(*key)) {
+                diff.push(format!("{path}.{key}: added"));
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                json_diff(a, b, &format!("{path}[{i}]"), diff);
+            }
+            if a.len() != b.len() {
+                diff.push(format!("{path}: length {} -> {}", a.len(), b.len()));
+            }
+        }
+        _ => {
+            if a != b {
+                diff.push(format!("{path}: {a} -> {b}"));
+            }
+        }
+    }
+}
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Compares the game states of two save states and lists the paths that differ,
+    /// in the form of `.field[index]: old -> new`.
+    pub fn diff_states(&self, a: usize, b: usize) -> Vec<String> {
+        let (Some(a), Some(b)) = (self.saved_states.get(a), self.saved_states.get(b)) else {
+            return Vec::new();
+        };
+        let values = serde_json::to_value(a.game_state())
+            .and_then(|a| Ok((a, serde_json::to_value(b.game_state())?)));
+        let (a, b) = match values {
+            Ok(values) => values,
+            Err(err) => {
+                log_error!("Failed to serialize states for comparison: {err}");
+                return Vec::new();
+            }
+        };
+
+        let mut diff = Vec::new();
+        json_diff(&a, &b, "", &mut diff);
+        diff
+    }
+}
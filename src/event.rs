@@ -0,0 +1,284 @@
+//! A stable serialized form of the recorded events.
+//!
+//! Run files outlive the geng version they were recorded with,
+//! so instead of relying on the layout of [geng::Event],
+//! the recorded events are stored in a format defined here,
+//! with keys and buttons stored under names of their own.
+
+use super::*;
+
+/// The serialized form of a recorded [geng::Event].
+///
+/// Keys and buttons are stored by name (e.g. `"A"`, `"Num1"`, `"Left"`),
+/// see [key_name] and [button_name].
+/// Events without a stable form (e.g. touches) are not recorded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StableEvent {
+    KeyDown {
+        key: String,
+    },
+    KeyUp {
+        key: String,
+    },
+    MouseDown {
+        position: [f64; 2],
+        button: String,
+    },
+    MouseUp {
+        position: [f64; 2],
+        button: String,
+    },
+    MouseMove {
+        position: [f64; 2],
+        delta: [f64; 2],
+    },
+    Wheel {
+        delta: f64,
+    },
+    GamepadButtonDown {
+        gamepad: usize,
+        button: String,
+    },
+    GamepadButtonUp {
+        gamepad: usize,
+        button: String,
+    },
+    GamepadAxis {
+        gamepad: usize,
+        axis: String,
+        value: f64,
+    },
+}
+
+impl StableEvent {
+    /// Converts the event into the stable format, if it has one.
+    pub fn from_event(event: &geng::Event) -> Option<Self> {
+        Some(match *event {
+            geng::Event::KeyDown { key } => Self::KeyDown { key: key_name(key) },
+            geng::Event::KeyUp { key } => Self::KeyUp { key: key_name(key) },
+            geng::Event::MouseDown { position, button } => Self::MouseDown {
+                position: [position.x, position.y],
+                button: button_name(button),
+            },
+            geng::Event::MouseUp { position, button } => Self::MouseUp {
+                position: [position.x, position.y],
+                button: button_name(button),
+            },
+            geng::Event::MouseMove { position, delta } => Self::MouseMove {
+                position: [position.x, position.y],
+                delta: [delta.x, delta.y],
+            },
+            geng::Event::Wheel { delta } => Self::Wheel { delta },
+            _ => return None,
+        })
+    }
+
+    /// Converts the event back, if the key or button is known to this version of geng.
+    /// Gamepad events are kept in runs but not replayed, as geng does not report them.
+    pub fn to_event(&self) -> Option<geng::Event> {
+        Some(match self {
+            Self::KeyDown { key } => geng::Event::KeyDown {
+                key: parse_key(key)?,
+            },
+            Self::KeyUp { key } => geng::Event::KeyUp {
+                key: parse_key(key)?,
+            },
+            &Self::MouseDown {
+                position: [x, y],
+                ref button,
+            } => geng::Event::MouseDown {
+                position: vec2(x, y),
+                button: parse_button(button)?,
+            },
+            &Self::MouseUp {
+                position: [x, y],
+                ref button,
+            } => geng::Event::MouseUp {
+                position: vec2(x, y),
+                button: parse_button(button)?,
+            },
+            &Self::MouseMove {
+                position: [x, y],
+                delta: [dx, dy],
+            } => geng::Event::MouseMove {
+                position: vec2(x, y),
+                delta: vec2(dx, dy),
+            },
+            &Self::Wheel { delta } => geng::Event::Wheel { delta },
+            Self::GamepadButtonDown { .. }
+            | Self::GamepadButtonUp { .. }
+            | Self::GamepadAxis { .. } => return None,
+        })
+    }
+}
+
+/// Defines the stored names of the variants of a geng enum, which stay the same
+/// even if geng renames the variants. Variants not listed use their serde name.
+macro_rules! stable_names {
+    ($($ty:ident)::+, $to_name:ident, $parse:ident, { $($name:literal => $variant:ident,)* }) => {
+        /// The name the value is stored under.
+        pub fn $to_name(value: $($ty)::+) -> String {
+            match value {
+                $($($ty)::+::$variant => $name.to_owned(),)*
+                #[allow(unreachable_patterns)]
+                value => serde_name(&value),
+            }
+        }
+
+        /// Finds the value stored under the name.
+        pub fn $parse(name: &str) -> Option<$($ty)::+> {
+            match name {
+                $($name => Some($($ty)::+::$variant),)*
+                name => serde_json::from_value(serde_json::Value::String(name.to_owned())).ok(),
+            }
+        }
+    };
+}
+
+stable_names!(geng::Key, key_name, parse_key, {
+    "A" => A, "B" => B, "C" => C, "D" => D, "E" => E, "F" => F, "G" => G,
+    "H" => H, "I" => I, "J" => J, "K" => K, "L" => L, "M" => M, "N" => N,
+    "O" => O, "P" => P, "Q" => Q, "R" => R, "S" => S, "T" => T, "U" => U,
+    "V" => V, "W" => W, "X" => X, "Y" => Y, "Z" => Z,
+    "Num0" => Num0, "Num1" => Num1, "Num2" => Num2, "Num3" => Num3, "Num4" => Num4,
+    "Num5" => Num5, "Num6" => Num6, "Num7" => Num7, "Num8" => Num8, "Num9" => Num9,
+    "Escape" => Escape, "Space" => Space, "Enter" => Enter, "Backspace" => Backspace,
+    "Tab" => Tab, "Period" => Period,
+    "LShift" => LShift, "RShift" => RShift, "LCtrl" => LCtrl, "RCtrl" => RCtrl,
+    "LAlt" => LAlt, "RAlt" => RAlt,
+    "Left" => Left, "Right" => Right, "Up" => Up, "Down" => Down,
+    "PageUp" => PageUp, "PageDown" => PageDown, "Home" => Home, "End" => End,
+    "Insert" => Insert, "Delete" => Delete,
+    "F1" => F1, "F2" => F2, "F3" => F3, "F4" => F4, "F5" => F5, "F6" => F6,
+    "F7" => F7, "F8" => F8, "F9" => F9, "F10" => F10, "F11" => F11, "F12" => F12,
+});
+
+stable_names!(geng::MouseButton, button_name, parse_button, {
+    "Left" => Left, "Right" => Right, "Middle" => Middle,
+});
+
+/// The name serde gives to a unit variant, or its debug name otherwise.
+fn serde_name(value: &(impl Serialize + std::fmt::Debug)) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => format!("{value:?}"),
+    }
+}
+
+/// An event in either the stable format or, for the runs saved before it, the raw one.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum AnyEvent {
+    Stable(StableEvent),
+    Raw(geng::Event),
+}
+
+impl AnyEvent {
+    /// Converts the event back, see [StableEvent::to_event].
+    pub fn to_event(&self) -> Option<geng::Event> {
+        match self {
+            Self::Stable(event) => event.to_event(),
+            Self::Raw(event) => Some(event.clone()),
+        }
+    }
+}
+
+/// Converts the recorded inputs into the stable format, leaving out the events without one.
+pub fn to_stable(inputs: &[FrameInput<geng::Event>]) -> Vec<FrameInput<StableEvent>> {
+    inputs
+        .iter()
+        .map(|input| {
+            input
+                .clone()
+                .filter_map(|event| StableEvent::from_event(&event))
+        })
+        .collect()
+}
+
+/// Converts the inputs read in either format back into events,
+/// leaving out the ones that cannot be replayed.
+pub fn from_any(inputs: Vec<FrameInput<AnyEvent>>) -> Vec<FrameInput<geng::Event>> {
+    let mut skipped = 0;
+    let inputs = inputs
+        .into_iter()
+        .map(|input| {
+            input.filter_map(|event| {
+                let event = event.to_event();
+                skipped += usize::from(event.is_none());
+                event
+            })
+        })
+        .collect();
+    if skipped > 0 {
+        log_warn!("Skipped {skipped} events that cannot be replayed (unknown keys or gamepads)");
+    }
+    inputs
+}
+
+/// Serializes the inputs in the stable format, for use with `#[serde(with = "stable_inputs")]`.
+pub mod stable_inputs {
+    use super::*;
+
+    pub fn serialize<S: serde::Serializer>(
+        inputs: &[FrameInput<geng::Event>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        to_stable(inputs).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<FrameInput<geng::Event>>, D::Error> {
+        Vec::<FrameInput<AnyEvent>>::deserialize(deserializer).map(from_any)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Saves and loads the inputs the way run files do.
+    pub(crate) fn round_trip(inputs: &[FrameInput<geng::Event>]) -> Vec<FrameInput<geng::Event>> {
+        let json = serde_json::to_string(&to_stable(inputs)).unwrap();
+        from_any(serde_json::from_str(&json).unwrap())
+    }
+
+    #[test]
+    fn keyboard_and_mouse_in_one_frame() {
+        let button = geng::MouseButton::Left;
+        let events = vec![
+            geng::Event::KeyDown { key: geng::Key::D },
+            geng::Event::MouseDown {
+                position: vec2(1.0, 1.0),
+                button,
+            },
+        ];
+        let inputs = round_trip(&[
+            FrameInput::new(1, events.clone()),
+            FrameInput::new(1, Vec::new()),
+        ]);
+        // Both are replayed in the original order and stay held afterwards
+        assert_eq!(inputs[0].inputs(), events);
+        let (keys, buttons) = pressed_at(&inputs, 2);
+        assert!(keys.contains(&geng::Key::D));
+        assert!(buttons.contains(&button));
+    }
+
+    #[test]
+    fn keyboard_and_gamepad_in_one_frame() {
+        let json = r#"{"frames": 2, "inputs": [
+            {"KeyDown": {"key": "D"}},
+            {"GamepadButtonDown": {"gamepad": 0, "button": "South"}}
+        ]}"#;
+        let input: FrameInput<StableEvent> = serde_json::from_str(json).unwrap();
+        // Both are kept in the file
+        let saved = serde_json::to_value(&input).unwrap();
+        assert_eq!(saved["inputs"].as_array().unwrap().len(), 2);
+        // Only the key is replayed, geng does not report gamepads
+        let events: Vec<_> = input.inputs().iter().map(StableEvent::to_event).collect();
+        assert_eq!(
+            events,
+            vec![Some(geng::Event::KeyDown { key: geng::Key::D }), None]
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use super::*;
+
+/// The largest dimension (in pixels) of an exported GIF frame.
+const GIF_MAX_SIZE: usize = 320;
+
+/// Starts the files written by [Tas::export_inputs], to tell them apart
+/// from the ones exported before, which only had the frames and the raw events.
+const EXPORT_MAGIC: [u8; 8] = *b"GTASIN02";
+
+/// A [FrameInput] as exported with [Tas::export_inputs].
+/// Bincode cannot skip fields, so unlike in run files all of them are always written.
+#[derive(Serialize, Deserialize)]
+struct ExportedInput {
+    frames: usize,
+    inputs: Vec<StableEvent>,
+    timestamps: Vec<f64>,
+    cursor: Option<vec2<f64>>,
+    delta_time: Option<f64>,
+}
+
+/// A [FrameInput] as exported before [EXPORT_MAGIC] was introduced.
+#[derive(Deserialize)]
+struct LegacyInput {
+    frames: usize,
+    inputs: Vec<geng::Event>,
+}
+
+/// The layout of the image exported with [Tas::export_input_image].
+#[derive(Debug, Clone)]
+pub struct InputImageOptions {
+    /// The width (in pixels) of a single frame.
+    pub frame_width: usize,
+    /// The largest width (in pixels) of the image.
+    /// Longer runs are squeezed, so that a column shows an input held on any of its frames.
+    pub max_width: usize,
+    /// The height (in pixels) of the row of a single key or button.
+    pub row_height: usize,
+}
+
+impl Default for InputImageOptions {
+    fn default() -> Self {
+        Self {
+            frame_width: 1,
+            max_width: 4096,
+            row_height: 8,
+        }
+    }
+}
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Exports only the recorded inputs in a compact binary format.
+    /// Such a file can be replayed from any matching initial state with [Self::import_inputs].
+    pub fn export_inputs(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Write;
+
+        let inputs: Vec<ExportedInput> = to_stable(&self.inputs)
+            .into_iter()
+            .map(|input| ExportedInput {
+                frames: input.frames,
+                inputs: input.inputs,
+                timestamps: input.timestamps,
+                cursor: input.cursor,
+                delta_time: input.delta_time,
+            })
+            .collect();
+        let file = std::fs::File::create(path)?;
+        let mut writer = std::io::BufWriter::new(file);
+        writer.write_all(&EXPORT_MAGIC)?;
+        bincode::serialize_into(&mut writer, &inputs)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Replays the inputs exported with [Self::export_inputs]
+    /// starting from the initial state of the current recording.
+    /// The files exported by older versions, without timestamps and such, are read as well.
+    pub fn import_inputs(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = std::fs::read(path)?;
+        let inputs = match bytes.strip_prefix(&EXPORT_MAGIC) {
+            Some(bytes) => {
+                let inputs: Vec<ExportedInput> = bincode::deserialize(bytes)?;
+                let inputs = inputs
+                    .into_iter()
+                    .map(|input| FrameInput {
+                        frames: input.frames,
+                        inputs: input.inputs.into_iter().map(AnyEvent::Stable).collect(),
+                        timestamps: input.timestamps,
+                        cursor: input.cursor,
+                        delta_time: input.delta_time,
+                    })
+                    .collect();
+                from_any(inputs)
+            }
+            None => {
+                let inputs: Vec<LegacyInput> = bincode::deserialize(&bytes)?;
+                inputs
+                    .into_iter()
+                    .map(|input| FrameInput::new(input.frames, input.inputs))
+                    .collect()
+            }
+        };
+
+        if self.initial_state().is_none() {
+            return Err("There is no initial state to replay the inputs from".into());
+        }
+        self.set_inputs(inputs);
+        Ok(())
+    }
+
+    /// Saves the run up to the current frame as a minimal reproduction of a game bug,
+    /// e.g. to attach to a bug report. The current frame is marked with a comment
+    /// containing the note, so that loading the file and replaying it to the end shows the bug.
+    /// The current recording is kept as is.
+    pub fn export_repro(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        note: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut saved = self.saved_run();
+        if let Some(replay) = &self.replay {
+            saved.history = replay.history.clone();
+            saved.inputs = replay.inputs.clone();
+            saved.render_hashes = replay.render_hashes.clone();
+            saved.frame_intervals = replay.frame_intervals.clone();
+        }
+        let frame = self.frame;
+        truncate_inputs(&mut saved.inputs, frame);
+        saved.history.truncate(frame);
+        saved.render_hashes.retain(|&hashed, _| hashed <= frame);
+        saved.frame_intervals.truncate(frame);
+        saved.total_frames = Some(total_frames(&saved.inputs));
+        saved.input_count = Some(saved.inputs.len());
+        saved.comments.retain(|(range, _)| range.start <= frame);
+        saved
+            .comments
+            .push((frame..frame + 1, format!("Bug: {note}")));
+
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, write_run::<T>(&saved, self.config.compact_json)?)?;
+        log_info!(
+            "Exported a reproduction up to frame {frame} to {}",
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Exports the recorded inputs as a CSV table with one row per frame.
+    /// Each key and mouse button used in the run gets a column
+    /// that is `1` on the frames it is held and `0` otherwise.
+    pub fn export_csv(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Write;
+
+        let (keys, buttons) = used_inputs(&self.inputs);
+
+        let file = std::fs::File::create(path)?;
+        let mut writer = std::io::BufWriter::new(file);
+        write!(writer, "frame,time_seconds")?;
+        for key in &keys {
+            write!(writer, ",{key:?}")?;
+        }
+        for button in &buttons {
+            write!(writer, ",Mouse{button:?}")?;
+        }
+        writeln!(writer)?;
+
+        let mut pressed_keys = HashSet::new();
+        let mut pressed_buttons = HashSet::new();
+        let mut frame = 0;
+        for input in &self.inputs {
+            for _ in 0..input.frames {
+                for event in &input.inputs {
+                    apply_pressed(event, &mut pressed_keys, &mut pressed_buttons);
+                }
+                write!(writer, "{frame},{}", frame as f64 * self.fixed_delta_time)?;
+                for key in &keys {
+                    write!(writer, ",{}", u8::from(pressed_keys.contains(key)))?;
+                }
+                for button in &buttons {
+                    write!(writer, ",{}", u8::from(pressed_buttons.contains(button)))?;
+                }
+                writeln!(writer)?;
+                frame += 1;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Exports the recorded inputs as Rust source code of a function with the given name
+    /// that returns them, so a run (e.g. a demo) can be compiled into the game and
+    /// replayed with [Self::set_inputs]. Only the frames and the key and mouse events are exported.
+    pub fn export_rust(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        function_name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Write;
+
+        let file = std::fs::File::create(path)?;
+        let mut writer = std::io::BufWriter::new(file);
+        writeln!(writer, "// Generated by geng-tas, do not edit.")?;
+        writeln!(
+            writer,
+            "pub fn {function_name}() -> Vec<geng_tas::FrameInput<geng::Event>> {{"
+        )?;
+        writeln!(writer, "    use geng::prelude::*;")?;
+        writeln!(writer, "    vec![")?;
+        for input in &self.inputs {
+            if input.inputs.is_empty() {
+                writeln!(
+                    writer,
+                    "        geng_tas::FrameInput::new({}, vec![]),",
+                    input.frames
+                )?;
+                continue;
+            }
+            writeln!(writer, "        geng_tas::FrameInput::new(")?;
+            writeln!(writer, "            {},", input.frames)?;
+            writeln!(writer, "            vec![")?;
+            for event in &input.inputs {
+                writeln!(writer, "                {},", rust_event(event)?)?;
+            }
+            writeln!(writer, "            ],")?;
+            writeln!(writer, "        ),")?;
+        }
+        writeln!(writer, "    ]")?;
+        writeln!(writer, "}}")?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Exports the recorded inputs as a PNG image with a row for each key and mouse button
+    /// used in the run (in the same order as the columns of [Self::export_csv]),
+    /// filled on the frames it is held.
+    pub fn export_input_image(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        options: &InputImageOptions,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (keys, buttons) = used_inputs(&self.inputs);
+        let total = total_frames(&self.inputs);
+        let rows = keys.len() + buttons.len();
+        if total == 0 || rows == 0 {
+            return Err("There are no held inputs to export".into());
+        }
+        let held = held_inputs(&self.inputs, 0..total);
+
+        let width = (total * options.frame_width.max(1)).min(options.max_width.max(1));
+        let row_height = options.row_height.max(1);
+        // Rows are separated by a line of background
+        let height = rows * (row_height + 1) - 1;
+        let mut pixels = vec![0x20; width * height * 3];
+        for x in 0..width {
+            let start = x * total / width;
+            let end = ((x + 1) * total / width).max(start + 1);
+            let frames = &held[start..end];
+            let is_held = keys
+                .iter()
+                .map(|key| frames.iter().any(|(keys, _)| keys.contains(key)))
+                .chain(
+                    buttons
+                        .iter()
+                        .map(|button| frames.iter().any(|(_, buttons)| buttons.contains(button))),
+                );
+            for (row, is_held) in is_held.enumerate() {
+                let color = if is_held {
+                    [0xF0, 0xC0, 0x40]
+                } else if row % 2 == 0 {
+                    [0x40, 0x40, 0x40]
+                } else {
+                    [0x30, 0x30, 0x30]
+                };
+                for y in row * (row_height + 1)..row * (row_height + 1) + row_height {
+                    let i = (y * width + x) * 3;
+                    pixels[i..i + 3].copy_from_slice(&color);
+                }
+            }
+        }
+
+        let file = std::fs::File::create(path)?;
+        let writer = std::io::BufWriter::new(file);
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+        Ok(())
+    }
+
+    /// Exports the current recording as a looping GIF.
+    /// Frames are taken from the recorded snapshots at (at most) the given `fps`,
+    /// at most `max_frames` of them, and rendered offscreen at a reduced resolution.
+    pub fn export_gif(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        fps: f64,
+        max_frames: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let fps = fps.max(1.0);
+        let interval = self.history.interval;
+        let step = ((1.0 / fps / self.fixed_delta_time).round() as usize / interval).max(1);
+
+        // Downscale the frames to keep the file size sane
+        let scale = (GIF_MAX_SIZE as f64
+            / self.framebuffer_size.x.max(self.framebuffer_size.y) as f64)
+            .min(1.0);
+        let size = self
+            .framebuffer_size
+            .map(|x| ((x as f64 * scale) as usize).max(1));
+
+        let file = std::fs::File::create(path)?;
+        let writer = std::io::BufWriter::new(file);
+        let mut encoder = gif::Encoder::new(writer, size.x as u16, size.y as u16, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        let geng = self.geng().clone();
+        let current = self.game.save();
+        let mut texture = ugli::Texture::new_uninitialized(geng.ugli(), size);
+        let mut result = Ok(());
+        for state in self.history.states.iter().step_by(step).take(max_frames) {
+            self.game.load(state.clone());
+
+            let mut pixels = Vec::with_capacity(size.x * size.y * 4);
+            {
+                let mut framebuffer = ugli::Framebuffer::new_color(
+                    geng.ugli(),
+                    ugli::ColorAttachment::Texture(&mut texture),
+                );
+                ugli::clear(&mut framebuffer, Some(Rgba::BLACK), None, None);
+                self.game.draw(&mut framebuffer);
+
+                // Framebuffer rows go bottom to top, gif rows go top to bottom
+                let read = framebuffer.read_color();
+                for y in (0..size.y).rev() {
+                    for x in 0..size.x {
+                        let color = read.get(x, y);
+                        pixels.extend([color.r, color.g, color.b, color.a]);
+                    }
+                }
+            }
+
+            let mut frame =
+                gif::Frame::from_rgba_speed(size.x as u16, size.y as u16, &mut pixels, 10);
+            frame.delay = ((step * interval) as f64 * self.fixed_delta_time * 100.0).round() as u16;
+            if let Err(err) = encoder.write_frame(&frame) {
+                result = Err(err.into());
+                break;
+            }
+        }
+        self.game.load(current);
+
+        result
+    }
+}
+
+/// Finds all the keys and buttons ever pressed in the inputs, sorted by name.
+fn used_inputs(inputs: &[FrameInput<geng::Event>]) -> (Vec<geng::Key>, Vec<geng::MouseButton>) {
+    let mut keys = HashSet::new();
+    let mut buttons = HashSet::new();
+    for event in inputs.iter().flat_map(|input| &input.inputs) {
+        match event {
+            geng::Event::KeyDown { key } | geng::Event::KeyUp { key } => {
+                keys.insert(*key);
+            }
+            geng::Event::MouseDown { button, .. } | geng::Event::MouseUp { button, .. } => {
+                buttons.insert(*button);
+            }
+            _ => {}
+        }
+    }
+    let mut keys: Vec<_> = keys.into_iter().collect();
+    keys.sort_by_key(|key| format!("{key:?}"));
+    let mut buttons: Vec<_> = buttons.into_iter().collect();
+    buttons.sort_by_key(|button| format!("{button:?}"));
+    (keys, buttons)
+}
+
+/// Formats the event as a Rust expression constructing it.
+fn rust_event(event: &geng::Event) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(match event {
+        geng::Event::KeyDown { key } => format!("geng::Event::KeyDown {{ key: geng::Key::{key:?} }}"),
+        geng::Event::KeyUp { key } => format!("geng::Event::KeyUp {{ key: geng::Key::{key:?} }}"),
+        geng::Event::MouseDown { position, button } => format!(
+            "geng::Event::MouseDown {{ position: vec2({:?}, {:?}), button: geng::MouseButton::{button:?} }}",
+            position.x, position.y
+        ),
+        geng::Event::MouseUp { position, button } => format!(
+            "geng::Event::MouseUp {{ position: vec2({:?}, {:?}), button: geng::MouseButton::{button:?} }}",
+            position.x, position.y
+        ),
+        geng::Event::MouseMove { position, delta } => format!(
+            "geng::Event::MouseMove {{ position: vec2({:?}, {:?}), delta: vec2({:?}, {:?}) }}",
+            position.x, position.y, delta.x, delta.y
+        ),
+        geng::Event::Wheel { delta } => format!("geng::Event::Wheel {{ delta: {delta:?} }}"),
+        event => return Err(format!("Cannot export {event:?} as Rust code").into()),
+    })
+}
@@ -0,0 +1,48 @@
+use super::*;
+
+/// Catches events fed back into the TAS while it simulates a frame.
+///
+/// Such events come from the game itself (e.g. re-dispatched from its `update`),
+/// so they are generated again on replay and must not be recorded.
+#[derive(Debug, Default)]
+pub(crate) struct SimulationGuard {
+    simulating: bool,
+}
+
+impl SimulationGuard {
+    /// Marks the start of simulating a frame.
+    pub fn enter(&mut self) {
+        self.simulating = true;
+    }
+
+    /// Marks the end of simulating a frame.
+    pub fn exit(&mut self) {
+        self.simulating = false;
+    }
+
+    /// Whether the event arrived outside of the simulation and can be recorded.
+    /// Warns about the events arriving during it.
+    pub fn accepts(&self, event: &geng::Event) -> bool {
+        if self.simulating {
+            log_warn!("The game fed {event:?} back into the TAS while simulating a frame");
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_during_simulation_rejected() {
+        let event = geng::Event::KeyDown { key: geng::Key::A };
+        let mut guard = SimulationGuard::default();
+        assert!(guard.accepts(&event));
+        guard.enter();
+        assert!(!guard.accepts(&event));
+        guard.exit();
+        assert!(guard.accepts(&event));
+    }
+}
@@ -0,0 +1,115 @@
+use super::*;
+
+fn default_interval() -> usize {
+    1
+}
+
+/// Periodic snapshots of the game state, used to restore and seek through a run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct History<T> {
+    /// The number of frames between consecutive snapshots.
+    #[serde(default = "default_interval", rename = "state_interval")]
+    pub interval: usize,
+    /// Snapshots of the game state at the start of every `interval`-th frame.
+    pub states: Vec<T>,
+    /// Snapshots taken on other frames with [History::force], sorted by frame.
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        rename = "extra_states"
+    )]
+    pub extra: Vec<(usize, T)>,
+}
+
+impl<T> History<T> {
+    pub fn new(interval: usize, initial_state: T) -> Self {
+        Self {
+            interval: interval.max(1),
+            states: vec![initial_state],
+            extra: Vec::new(),
+        }
+    }
+
+    /// The state at the start of the run.
+    pub fn first(&self) -> Option<&T> {
+        self.states.first()
+    }
+
+    /// The most recent snapshot.
+    pub fn last(&self) -> Option<&T> {
+        self.states.last()
+    }
+
+    /// Stores the state at the start of the given frame, if a snapshot is due on that frame.
+    pub fn record(&mut self, frame: usize, state: impl FnOnce() -> T) {
+        if frame % self.interval == 0 && frame / self.interval == self.states.len() {
+            self.states.push(state());
+        }
+    }
+
+    /// Stores the state at the start of the given frame even if no snapshot is due,
+    /// replacing the one taken on that frame.
+    pub fn force(&mut self, frame: usize, state: T) {
+        if frame % self.interval == 0 {
+            if let Some(snapshot) = self.states.get_mut(frame / self.interval) {
+                *snapshot = state;
+                return;
+            }
+        }
+        let index = self.extra.partition_point(|&(taken, _)| taken < frame);
+        match self.extra.get_mut(index) {
+            Some((taken, snapshot)) if *taken == frame => *snapshot = state,
+            _ => self.extra.insert(index, (frame, state)),
+        }
+    }
+
+    /// Returns the snapshot taken exactly at the start of the given frame.
+    pub fn get_exact(&self, frame: usize) -> Option<&T> {
+        let regular = if frame % self.interval == 0 {
+            self.states.get(frame / self.interval)
+        } else {
+            None
+        };
+        regular.or_else(|| {
+            let index = self
+                .extra
+                .binary_search_by_key(&frame, |&(taken, _)| taken)
+                .ok()?;
+            Some(&self.extra[index].1)
+        })
+    }
+
+    /// Returns the latest snapshot at or before the given frame along with the frame it was taken at.
+    pub fn nearest(&self, frame: usize) -> Option<(usize, &T)> {
+        let regular = self.states.len().checked_sub(1).map(|last| {
+            let index = (frame / self.interval).min(last);
+            (index * self.interval, &self.states[index])
+        });
+        let extra = self.extra[..self.extra.partition_point(|&(taken, _)| taken <= frame)]
+            .last()
+            .map(|(taken, state)| (*taken, state));
+        regular
+            .into_iter()
+            .chain(extra)
+            .max_by_key(|&(taken, _)| taken)
+    }
+
+    /// Removes all snapshots taken after the given frame.
+    pub fn truncate(&mut self, frame: usize) {
+        self.states.truncate(frame / self.interval + 1);
+        self.extra.retain(|&(taken, _)| taken <= frame);
+    }
+
+    /// Removes all snapshots.
+    pub fn clear(&mut self) {
+        self.states.clear();
+        self.extra.clear();
+    }
+
+    /// All the snapshots, the regular ones first.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.states
+            .iter_mut()
+            .chain(self.extra.iter_mut().map(|(_, state)| state))
+    }
+}
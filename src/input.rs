@@ -0,0 +1,447 @@
+use super::*;
+
+/// Returns the total number of frames covered by the inputs.
+pub fn total_frames<T>(inputs: &[FrameInput<T>]) -> usize {
+    inputs.iter().map(|input| input.frames).sum()
+}
+
+/// Returns the index of the input active at the given frame
+/// and the number of frames (including the given one) until the next input.
+pub fn input_position<T>(inputs: &[FrameInput<T>], frame: usize) -> (usize, usize) {
+    let mut start = 0;
+    for (i, input) in inputs.iter().enumerate() {
+        let end = start + input.frames;
+        if frame < end {
+            return (i, end - frame);
+        }
+        start = end;
+    }
+    (inputs.len(), 0)
+}
+
+/// Updates the pressed keys and buttons according to the event.
+pub fn apply_pressed(
+    event: &geng::Event,
+    pressed_keys: &mut HashSet<geng::Key>,
+    pressed_buttons: &mut HashSet<geng::MouseButton>,
+) {
+    match event {
+        geng::Event::KeyDown { key } => {
+            pressed_keys.insert(*key);
+        }
+        geng::Event::KeyUp { key } => {
+            pressed_keys.remove(key);
+        }
+        geng::Event::MouseDown { button, .. } => {
+            pressed_buttons.insert(*button);
+        }
+        geng::Event::MouseUp { button, .. } => {
+            pressed_buttons.remove(button);
+        }
+        _ => {}
+    }
+}
+
+/// Whether the key is held once the queued events are applied.
+pub fn key_held(pressed_keys: &HashSet<geng::Key>, queued: &[geng::Event], key: geng::Key) -> bool {
+    let mut held = pressed_keys.contains(&key);
+    for event in queued {
+        match *event {
+            geng::Event::KeyDown { key: pressed } if pressed == key => held = true,
+            geng::Event::KeyUp { key: released } if released == key => held = false,
+            _ => {}
+        }
+    }
+    held
+}
+
+/// Whether `release` releases the key or button pressed by `press`.
+fn is_release_of(press: &geng::Event, release: &geng::Event) -> bool {
+    match (press, release) {
+        (geng::Event::KeyDown { key: pressed }, geng::Event::KeyUp { key: released }) => {
+            pressed == released
+        }
+        (
+            geng::Event::MouseDown {
+                button: pressed, ..
+            },
+            geng::Event::MouseUp {
+                button: released, ..
+            },
+        ) => pressed == released,
+        _ => false,
+    }
+}
+
+/// Removes the keys and buttons that are pressed and then released within the same frame.
+pub fn remove_taps(events: &mut Vec<geng::Event>) {
+    let mut removed = vec![false; events.len()];
+    for press in 0..events.len() {
+        if removed[press] {
+            continue;
+        }
+        let release = (press + 1..events.len())
+            .find(|&i| !removed[i] && is_release_of(&events[press], &events[i]));
+        if let Some(release) = release {
+            removed[press] = true;
+            removed[release] = true;
+        }
+    }
+
+    let mut removed = removed.into_iter();
+    events.retain(|_| !removed.next().unwrap());
+}
+
+/// The position of the mouse event, if it has one.
+pub fn event_position(event: &geng::Event) -> Option<vec2<f64>> {
+    match *event {
+        geng::Event::MouseDown { position, .. }
+        | geng::Event::MouseUp { position, .. }
+        | geng::Event::MouseMove { position, .. } => Some(position),
+        _ => None,
+    }
+}
+
+/// Finds the last known cursor position at the start of the given frame,
+/// from the recorded cursor and the positions of the mouse events.
+pub fn cursor_at(inputs: &[FrameInput<geng::Event>], frame: usize) -> Option<vec2<f64>> {
+    let mut cursor = None;
+    let mut start = 0;
+    for input in inputs {
+        if start >= frame {
+            break;
+        }
+        cursor = input.cursor.or(cursor);
+        cursor = input
+            .inputs
+            .iter()
+            .filter_map(event_position)
+            .last()
+            .or(cursor);
+        start += input.frames;
+    }
+    cursor
+}
+
+/// Collects the keys and buttons held at the start of the given frame.
+pub fn pressed_at(
+    inputs: &[FrameInput<geng::Event>],
+    frame: usize,
+) -> (HashSet<geng::Key>, HashSet<geng::MouseButton>) {
+    let mut pressed_keys = HashSet::new();
+    let mut pressed_buttons = HashSet::new();
+    let mut start = 0;
+    for input in inputs {
+        if start >= frame {
+            break;
+        }
+        // Repeating the same events over several frames results in the same pressed state,
+        // so they only need to be applied once
+        for event in &input.inputs {
+            apply_pressed(event, &mut pressed_keys, &mut pressed_buttons);
+        }
+        start += input.frames;
+    }
+    (pressed_keys, pressed_buttons)
+}
+
+/// Collects the keys and buttons held during each of the frames in the range.
+pub fn held_inputs(
+    inputs: &[FrameInput<geng::Event>],
+    frames: Range<usize>,
+) -> Vec<(HashSet<geng::Key>, HashSet<geng::MouseButton>)> {
+    let (mut pressed_keys, mut pressed_buttons) = pressed_at(inputs, frames.start);
+    frame_inputs(inputs, frames)
+        .into_iter()
+        .map(|events| {
+            for event in &events {
+                apply_pressed(event, &mut pressed_keys, &mut pressed_buttons);
+            }
+            (pressed_keys.clone(), pressed_buttons.clone())
+        })
+        .collect()
+}
+
+/// Removes all inputs starting from the given frame, splitting the input at the boundary.
+pub fn truncate_inputs<T>(inputs: &mut Vec<FrameInput<T>>, frame: usize) {
+    let (index, left) = input_position(inputs, frame);
+    if let Some(input) = inputs.get_mut(index) {
+        input.frames -= left;
+        let keep = if input.frames == 0 { index } else { index + 1 };
+        inputs.truncate(keep);
+    }
+}
+
+/// Expands the inputs in the given frame range into a list of events per frame.
+pub fn frame_inputs<T: Clone>(inputs: &[FrameInput<T>], frames: Range<usize>) -> Vec<Vec<T>> {
+    expand_frames(inputs, frames, |input| input.inputs.clone())
+}
+
+/// Expands the inputs in the given frame range into single-frame inputs,
+/// keeping everything recorded along with the events.
+pub fn expand_inputs<T: Clone>(
+    inputs: &[FrameInput<T>],
+    frames: Range<usize>,
+) -> Vec<FrameInput<T>> {
+    expand_frames(inputs, frames, |input| FrameInput {
+        frames: 1,
+        ..input.clone()
+    })
+}
+
+fn expand_frames<T, U>(
+    inputs: &[FrameInput<T>],
+    frames: Range<usize>,
+    expand: impl Fn(&FrameInput<T>) -> U,
+) -> Vec<U> {
+    let mut result = Vec::with_capacity(frames.len());
+    let mut start = 0;
+    for input in inputs {
+        let end = start + input.frames;
+        let from = start.max(frames.start);
+        let to = end.min(frames.end);
+        for _ in from..to {
+            result.push(expand(input));
+        }
+        if end >= frames.end {
+            break;
+        }
+        start = end;
+    }
+    result
+}
+
+/// Compresses a list of events per frame into repeated inputs.
+pub fn compress_inputs<T: PartialEq>(frames: Vec<Vec<T>>) -> Vec<FrameInput<T>> {
+    let mut result: Vec<FrameInput<T>> = Vec::new();
+    for inputs in frames {
+        if let Some(last) = result.last_mut().filter(|last| last.inputs == inputs) {
+            last.frames += 1;
+        } else {
+            result.push(FrameInput::new(1, inputs));
+        }
+    }
+    result
+}
+
+/// Merges adjacent inputs that are the same.
+pub fn merge_inputs<T: PartialEq>(inputs: &mut Vec<FrameInput<T>>) {
+    let mut merged: Vec<FrameInput<T>> = Vec::with_capacity(inputs.len());
+    for input in inputs.drain(..) {
+        if input.frames == 0 {
+            continue;
+        }
+        match merged.last_mut() {
+            Some(last) if last.same_as(&input) => last.frames += input.frames,
+            _ => merged.push(input),
+        }
+    }
+    *inputs = merged;
+}
+
+/// Replaces the events of a single frame, splitting the input containing it.
+/// Frames past the end are filled with empty inputs.
+pub fn set_frame<T: Clone + PartialEq>(
+    inputs: &mut Vec<FrameInput<T>>,
+    frame: usize,
+    events: Vec<T>,
+) {
+    let (index, left) = input_position(inputs, frame);
+    match inputs.get(index) {
+        None => {
+            let total = total_frames(inputs);
+            inputs.push(FrameInput::new(frame - total, Vec::new()));
+            inputs.push(FrameInput::new(1, events));
+        }
+        Some(input) => {
+            let old = input.clone();
+            let replacement = [
+                FrameInput {
+                    frames: input.frames - left,
+                    ..old.clone()
+                },
+                FrameInput::new(1, events),
+                FrameInput {
+                    frames: left - 1,
+                    ..old
+                },
+            ];
+            inputs.splice(index..=index, replacement);
+        }
+    }
+    merge_inputs(inputs);
+}
+
+/// Removes the frames in the given range, joining the inputs around it.
+pub fn remove_frames<T: PartialEq>(inputs: &mut Vec<FrameInput<T>>, frames: Range<usize>) {
+    let mut start = 0;
+    for input in inputs.iter_mut() {
+        let end = start + input.frames;
+        input.frames -= end.min(frames.end).saturating_sub(start.max(frames.start));
+        start = end;
+    }
+    merge_inputs(inputs);
+}
+
+/// Splits the frames into (at most) `buckets` equal parts
+/// and computes the fraction of frames with any events in each of them.
+pub fn input_density<T>(inputs: &[FrameInput<T>], buckets: usize) -> Vec<f64> {
+    let total = total_frames(inputs);
+    if total == 0 || buckets == 0 {
+        return Vec::new();
+    }
+    let bucket_size = total.div_ceil(buckets);
+    let mut active = vec![0; total.div_ceil(bucket_size)];
+
+    let mut start = 0;
+    for input in inputs {
+        let end = start + input.frames;
+        if !input.inputs.is_empty() {
+            let mut frame = start;
+            while frame < end {
+                let bucket = frame / bucket_size;
+                let bucket_end = ((bucket + 1) * bucket_size).min(end);
+                active[bucket] += bucket_end - frame;
+                frame = bucket_end;
+            }
+        }
+        start = end;
+    }
+
+    active
+        .into_iter()
+        .enumerate()
+        .map(|(i, active)| {
+            let size = bucket_size.min(total - i * bucket_size);
+            active as f64 / size as f64
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::tests::round_trip;
+
+    fn key_down(key: geng::Key) -> geng::Event {
+        geng::Event::KeyDown { key }
+    }
+
+    #[test]
+    fn click_drag_positions() {
+        let button = geng::MouseButton::Left;
+        let frames = vec![
+            vec![geng::Event::MouseDown {
+                position: vec2(1.0, 1.0),
+                button,
+            }],
+            vec![geng::Event::MouseMove {
+                position: vec2(2.0, 1.5),
+                delta: vec2(1.0, 0.5),
+            }],
+            Vec::new(),
+            vec![geng::Event::MouseUp {
+                position: vec2(3.0, 2.0),
+                button,
+            }],
+        ];
+        let inputs = round_trip(&compress_inputs(frames.clone()));
+        assert_eq!(frame_inputs(&inputs, 0..4), frames);
+
+        assert_eq!(cursor_at(&inputs, 0), None);
+        let positions = [(1.0, 1.0), (2.0, 1.5), (2.0, 1.5), (3.0, 2.0)];
+        for (frame, (x, y)) in (1..).zip(positions) {
+            assert_eq!(cursor_at(&inputs, frame), Some(vec2(x, y)));
+        }
+        // The button is held while dragging
+        assert!(pressed_at(&inputs, 2).1.contains(&button));
+        assert!(pressed_at(&inputs, 4).1.is_empty());
+    }
+
+    #[test]
+    fn event_order_kept() {
+        let events = vec![
+            geng::Event::KeyUp { key: geng::Key::A },
+            geng::Event::MouseMove {
+                position: vec2(1.0, 2.0),
+                delta: vec2(1.0, 0.0),
+            },
+            key_down(geng::Key::B),
+            key_down(geng::Key::A),
+        ];
+        let frames = vec![events.clone(), events, Vec::new()];
+        let inputs = round_trip(&compress_inputs(frames.clone()));
+        assert_eq!(frame_inputs(&inputs, 0..3), frames);
+    }
+
+    #[test]
+    fn repeated_events_replayed_every_frame() {
+        // The same event on consecutive frames, e.g. a key repeated while typing
+        let frames = vec![vec![key_down(geng::Key::A)]; 3];
+        let inputs = compress_inputs(frames.clone());
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].frames(), 3);
+        assert_eq!(frame_inputs(&inputs, 0..3), frames);
+    }
+
+    #[test]
+    fn recorded_cursor_positions() {
+        let mut inputs = vec![
+            FrameInput::new(2, Vec::new()),
+            FrameInput::new(1, Vec::new()),
+            FrameInput::new(1, Vec::new()),
+        ];
+        inputs[0].cursor = Some(vec2(5.0, 5.0));
+        inputs[2].cursor = Some(vec2(7.0, 6.0));
+        let inputs = round_trip(&inputs);
+        // The cursor stays where it was last recorded
+        assert_eq!(cursor_at(&inputs, 0), None);
+        let positions = [(5.0, 5.0), (5.0, 5.0), (5.0, 5.0), (7.0, 6.0)];
+        for (frame, (x, y)) in (1..).zip(positions) {
+            assert_eq!(cursor_at(&inputs, frame), Some(vec2(x, y)));
+        }
+    }
+
+    #[test]
+    fn key_held_across_steps() {
+        // A key pressed in the menu is recorded on the first step and stays held after it
+        let inputs = compress_inputs(vec![vec![key_down(geng::Key::D)], Vec::new(), Vec::new()]);
+        let held = held_inputs(&inputs, 0..3);
+        assert_eq!(held.len(), 3);
+        assert!(held.iter().all(|(keys, _)| keys.contains(&geng::Key::D)));
+    }
+
+    #[test]
+    fn same_frame_tap_removed() {
+        let key_up = |key| geng::Event::KeyUp { key };
+        let mut events = vec![
+            key_down(geng::Key::A),
+            key_down(geng::Key::B),
+            key_up(geng::Key::A),
+            key_up(geng::Key::C),
+        ];
+        remove_taps(&mut events);
+        // Only the tap of A is removed, the press of B and the release of C are kept
+        assert_eq!(events, vec![key_down(geng::Key::B), key_up(geng::Key::C)]);
+    }
+
+    #[test]
+    fn remove_frames_joins_inputs() {
+        let a = key_down(geng::Key::A);
+        let b = key_down(geng::Key::B);
+        let mut inputs = compress_inputs(vec![
+            vec![a.clone()],
+            vec![a.clone()],
+            vec![b],
+            vec![a.clone()],
+            Vec::new(),
+        ]);
+        remove_frames(&mut inputs, 1..3);
+        assert_eq!(
+            frame_inputs(&inputs, 0..3),
+            vec![vec![a.clone()], vec![a], Vec::new()]
+        );
+        // Merged back into a single input
+        assert_eq!(inputs.len(), 2);
+    }
+}
@@ -1,80 +1,569 @@
 use geng::prelude::*;
 
+#[macro_use]
+mod logs;
+
+mod bench;
+mod clock;
+mod commands;
+mod comments;
+mod compare;
+mod config;
+mod diff;
+mod event;
+mod export;
+mod guard;
+mod history;
+mod input;
+mod macros;
+mod recovery;
+mod reference;
+mod render_hash;
+mod slots;
+mod storage;
+mod stream;
+mod toast;
 mod ui;
 
+pub use bench::BenchReport;
+use clock::*;
+use compare::*;
+pub use config::*;
+use event::*;
+pub use export::InputImageOptions;
+
+use guard::*;
+use history::*;
+use input::*;
+use macros::*;
+use slots::*;
+use storage::*;
+use stream::*;
+use toast::*;
+
 /// A wrapper for a game that implements TAS functionality:
 /// save states, slow motion, input replay.
 pub struct Tas<T: Tasable> {
-    geng: Geng,
+    /// Missing only in tests, which run without a window.
+    geng: Option<Geng>,
+    config: TasConfig,
     framebuffer_size: vec2<usize>,
     /// The game state that is manipulated.
     game: T,
     show_ui: bool,
+    /// Whether the overlay is disabled for good, see [TasConfig::disable_ui].
+    ui_disabled: bool,
+    /// Whether [Keybinds::peek_ui] is held.
+    ui_peeking: bool,
     /// Multiplier for `delta_time`, used for slow-motion.
     time_scale: f64,
+    /// The time scale to switch to with [Keybinds::slow_motion].
+    slow_time_scale: f64,
     paused: bool,
-    /// Paused when pressing the LAlt key.
-    auto_paused: bool,
+    /// Whether the replay is played backwards, see [Tas::set_reverse].
+    reversing: bool,
+    /// Whether the TAS menu is open, capturing the input and pausing the simulation.
+    menu_open: bool,
     /// The expected time between fixed updates.
     fixed_delta_time: f64,
+    /// The time between geng's fixed updates, which differs from `fixed_delta_time`
+    /// with [TasConfig::frame_rate].
+    engine_delta_time: f64,
     /// All saved states.
     saved_states: Vec<SaveState<T::Saved>>,
+    /// Whether the save states have changed since they were last written to disk.
+    states_dirty: bool,
+    /// Time (in seconds) since the save states were last written to disk.
+    states_flush_timer: f64,
+    /// Time since the window title was last updated, see [TasConfig::window_title].
+    title_timer: f64,
+    /// Recently deleted states along with their former positions.
+    deleted_states: VecDeque<(usize, SaveState<T::Saved>)>,
     /// The state that is loaded on <C-l>.
     selected_state: usize,
+    /// The last loaded save state, until new inputs are recorded after it.
+    loaded_state: Option<usize>,
     /// Current simulation frame.
     frame: usize,
     /// History of all inputs.
     inputs: Vec<FrameInput<geng::Event>>,
-    /// History of all states.
-    states: Vec<T::Saved>,
+    /// Snapshots of the recorded states.
+    history: History<T::Saved>,
     save_file: String,
+    /// Run files found next to the save file.
+    run_files: Vec<String>,
+    /// Whether a text field in the UI is being edited, so the input should not be handled.
+    text_focused: bool,
+    console_open: bool,
+    show_help: bool,
+    /// Whether the recent log messages are shown, see [Keybinds::log_view].
+    show_log: bool,
+    /// Whether the held inputs are listed, see [Keybinds::input_overlay].
+    show_input_overlay: bool,
+    /// The save state selected to be compared with another one.
+    diff_base: Option<usize>,
+    /// Differences between the last compared save states.
+    state_diff: Vec<String>,
+    /// The text typed into the frame number field.
+    goto_input: String,
+    /// The text typed into the time field.
+    goto_time_input: String,
+    console_input: String,
+    /// Recent commands and their output.
+    console_log: VecDeque<String>,
     replay: Option<Replay<T::Saved>>,
     // initial_state: T::Saved,
-    acc_delta_time: f64,
+    /// The time not yet simulated, see [FrameClock].
+    clock: FrameClock,
+    /// Set while a frame is simulated, see [Tasable].
+    simulation: SimulationGuard,
     queued_inputs: Vec<geng::Event>,
+    /// The arrival times of the queued inputs, see [TasConfig::record_timestamps].
+    queued_timestamps: Vec<f64>,
+    /// The real time since the last frame was simulated, to timestamp the queued inputs with.
+    frame_time: f64,
+    /// The real delta time to update the game with on the next frame,
+    /// see [TasConfig::record_delta_time].
+    frame_delta_time: Option<f64>,
+    /// The cursor position to record for the next frame instead of the current one.
+    cursor_override: Option<vec2<f64>>,
+    /// The cursor position in the simulation, as last recorded or given with a mouse event.
+    cursor_position: Option<vec2<f64>>,
     /// All pressed keyboard keys in the simulation.
     pressed_keys: HashSet<geng::Key>,
     /// All pressed mouse buttons in the simulation.
     pressed_buttons: HashSet<geng::MouseButton>,
+    /// Whether the window supports setting the pressed keys and buttons.
+    window_sync_supported: bool,
+    /// Pressed keys as last seen by the game.
+    synced_keys: HashSet<geng::Key>,
+    /// Pressed buttons as last seen by the game.
+    synced_buttons: HashSet<geng::MouseButton>,
+    /// When set, states received from this channel replace the simulation.
+    external_states: Option<std::sync::mpsc::Receiver<T::Saved>>,
+    /// Reusable input sequences by name.
+    macros: HashMap<String, Vec<FrameInput<geng::Event>>>,
+    /// The macro played with the hotkey.
+    selected_macro: Option<String>,
+    macro_recording: Option<MacroRecording>,
+    /// Inputs of the macro being played, one entry per frame.
+    pending_macro: VecDeque<Vec<geng::Event>>,
+    /// Whether to continue recording once the current replay ends, see [Self::watch_recording].
+    continue_after_replay: bool,
+    /// The runs to replay after the current one.
+    playlist: VecDeque<std::path::PathBuf>,
+    /// On-screen notifications, oldest first.
+    toasts: VecDeque<Toast>,
+    /// The second run replayed alongside the main one, see [Tas::compare_runs].
+    comparison: Option<Comparison<T>>,
+    /// The inputs of the run to compare the recording against.
+    reference: Option<Vec<FrameInput<geng::Event>>>,
+    /// Whether the held inputs matched the reference run on the last recorded frame.
+    reference_match: Option<bool>,
+    /// The last frame a desync was detected on.
+    desync_frame: Option<usize>,
+    /// Hashes of the frames drawn while recording.
+    render_hashes: BTreeMap<usize, u64>,
+    /// The real time before each recorded frame, see [TasConfig::record_pacing].
+    frame_intervals: Vec<f64>,
+    /// The real time since the last recorded frame.
+    pacing_time: f64,
+    /// Whether the recorded frames are being simulated again, see [Tas::seek],
+    /// so that only their inputs are recorded and not the real-time data.
+    resimulating: bool,
+    /// The last frame a hash has been computed for.
+    last_hashed_frame: Option<usize>,
+    /// Notes attached to frame ranges of the run, see [Tas::add_comment].
+    comments: Vec<(std::ops::Range<usize>, String)>,
+    /// The text typed into the comment field.
+    comment_input: String,
+    /// The frame the run should end before, see [Tas::set_goal_frame].
+    goal_frame: Option<usize>,
+    /// Whether the game reported the run as finished on the last recorded frame.
+    finished: bool,
+    /// Whether the game asked for an autosave on the last recorded frame,
+    /// see [Tasable::should_autosave].
+    last_autosave_condition: bool,
+    /// The state of the game's RNG at the start of the run, see [Tasable::save_rng].
+    initial_rng: Vec<u8>,
+    /// The frames replayed over and over, see [Tas::loop_range].
+    looped_range: Option<std::ops::Range<usize>>,
+    /// The state before the last simulated frame, used for interpolation.
+    previous_state: Option<T::Saved>,
+    /// Whether the recording log has to be rewritten instead of appended to.
+    log_dirty: bool,
+    /// Called after every simulated frame.
+    frame_callback: Option<Box<dyn FnMut(usize, &T)>>,
+    /// The number of frames simulated by [geng::State::fixed_update] since the last update.
+    burst_frames: usize,
+    /// The total number of frames simulated to catch up with real time.
+    lag_frames: usize,
+    /// Whether the last update had to catch up.
+    lagging: bool,
+    /// The number of times the recording has been redone from an earlier point.
+    rerecords: usize,
+    /// The name of the current recording, see [Tas::switch_recording].
+    recording_slot: String,
+    /// The other recordings by name.
+    recording_slots: HashMap<String, RecordingSlot<T::Saved>>,
+    /// The inputs recorded before the last load, compared against to detect a rerecord.
+    rerecord_reference: Option<Vec<FrameInput<geng::Event>>>,
+    /// Conditions that pause the simulation once they hold.
+    breakpoints: Vec<Box<dyn Fn(&T) -> bool>>,
+    /// The frame to pause at, see [Tas::break_before_input].
+    input_breakpoint: Option<usize>,
+    /// Whether the initial state is yet to be captured, see [TasConfig::defer_initial_state].
+    initial_state_pending: bool,
+    /// The frame hovered on the timeline.
+    preview_frame: Option<usize>,
+    /// The rendered preview of a frame, see [TasConfig::scrub_preview].
+    preview: Option<(usize, ugli::Texture)>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct SavedTas<T> {
+    /// A description of the format, missing in older runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    header: Option<RunHeader>,
+    /// The time between frames at the moment of recording.
+    #[serde(default)]
+    fixed_delta_time: Option<f64>,
+    /// The framebuffer size at the moment of recording.
+    #[serde(default)]
+    framebuffer_size: Option<vec2<usize>>,
+    /// The number of rerecords made while recording the run.
+    #[serde(default)]
+    rerecords: usize,
+    /// The number of frames in the run, to detect truncated or corrupted files.
+    #[serde(default)]
+    total_frames: Option<usize>,
+    /// The number of entries in `inputs`, to detect truncated or corrupted files.
+    #[serde(default)]
+    input_count: Option<usize>,
+    /// Hashes of the drawn frames, see [TasConfig::render_hash_interval].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    render_hashes: BTreeMap<usize, u64>,
+    /// The real time before each frame, see [TasConfig::record_pacing].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    frame_intervals: Vec<f64>,
+    /// Notes attached to frame ranges, see [Tas::add_comment].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    comments: Vec<(std::ops::Range<usize>, String)>,
+    /// The frame the run should end before, see [Tas::set_goal_frame].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    goal_frame: Option<usize>,
+    /// The state of the game's RNG at the start of the run, see [Tasable::save_rng].
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "base64_bytes")]
+    rng: Vec<u8>,
+    /// The format of the states, see [Tasable::saved_version].
+    #[serde(default)]
+    version: u32,
+    /// The hash of the save state the run starts from, see [TasConfig::relative_runs].
+    /// If set, the initial state is left out of `history`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_state: Option<u64>,
     // initial_state: T,
-    states: Vec<T>,
+    #[serde(flatten)]
+    history: History<T>,
+    #[serde(with = "stable_inputs")]
     inputs: Vec<FrameInput<geng::Event>>,
 }
 
+impl<T> SavedTas<T> {
+    /// Warns if the inputs do not match the recorded counts.
+    fn verify(&self) {
+        let total_frames = total_frames(&self.inputs);
+        if let Some(expected) = self.total_frames {
+            if expected != total_frames {
+                log_warn!(
+                    "The run should have {expected} frames, but has {total_frames}, \
+                    the file may be truncated or corrupted"
+                );
+            }
+        }
+        if let Some(expected) = self.input_count {
+            if expected != self.inputs.len() {
+                log_warn!(
+                    "The run should have {expected} inputs, but has {}, \
+                    the file may be truncated or corrupted",
+                    self.inputs.len()
+                );
+            }
+        }
+    }
+}
+
 struct Replay<T> {
     /// Current frame index.
     frame: usize,
-    states: Vec<T>,
+    history: History<T>,
     /// Current input index.
     input: usize,
     /// The amount of frames until next input should be taken.
     next_input: usize,
     inputs: Vec<FrameInput<geng::Event>>,
+    /// The number of frames in `inputs`.
+    total_frames: usize,
+    /// The recorded hashes of the drawn frames.
+    render_hashes: BTreeMap<usize, u64>,
+    /// The recorded real time before each frame.
+    frame_intervals: Vec<f64>,
+    /// The inputs yet to be read from the file, when streaming the run.
+    stream: Option<InputStream>,
+    /// The number of times the replay has been restarted after a desync,
+    /// see [DesyncPolicy::RetryFromStart].
+    retries: usize,
+}
+
+impl<T> Replay<T> {
+    fn new(history: History<T>, inputs: Vec<FrameInput<geng::Event>>) -> Self {
+        Self {
+            frame: 0,
+            history,
+            input: 0,
+            next_input: inputs.first().map(|input| input.frames).unwrap_or(0),
+            total_frames: total_frames(&inputs),
+            render_hashes: BTreeMap::new(),
+            frame_intervals: Vec::new(),
+            inputs,
+            stream: None,
+            retries: 0,
+        }
+    }
 }
 
+/// Inputs repeated over several consecutive frames.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct FrameInput<T> {
+pub struct FrameInput<T> {
     /// How long should these inputs be replayed for.
     frames: usize,
+    /// Events applied on each of the frames, in the order they arrived.
     inputs: Vec<T>,
+    /// Optional times (in seconds) since the start of the frame at which each event arrived,
+    /// recorded with [TasConfig::record_timestamps].
+    /// If there is one for each event, the events are applied in their order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    timestamps: Vec<f64>,
+    /// The cursor position during these frames, recorded with [TasConfig::record_cursor].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cursor: Option<vec2<f64>>,
+    /// The delta time passed to `update` on these frames,
+    /// recorded with [TasConfig::record_delta_time].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    delta_time: Option<f64>,
+}
+
+impl<T> FrameInput<T> {
+    pub fn new(frames: usize, inputs: Vec<T>) -> Self {
+        Self {
+            frames,
+            inputs,
+            timestamps: Vec::new(),
+            cursor: None,
+            delta_time: None,
+        }
+    }
+
+    /// Whether the inputs are the same apart from their duration.
+    pub(crate) fn same_as(&self, other: &Self) -> bool
+    where
+        T: PartialEq,
+    {
+        self.inputs == other.inputs
+            && self.timestamps == other.timestamps
+            && self.cursor == other.cursor
+            && self.delta_time == other.delta_time
+    }
+
+    /// The number of consecutive frames these inputs are applied on.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// The events applied on each of the frames.
+    pub fn inputs(&self) -> &[T] {
+        &self.inputs
+    }
+
+    /// Converts the events, keeping the rest as is.
+    pub(crate) fn map<U>(self, f: impl FnMut(T) -> U) -> FrameInput<U> {
+        FrameInput {
+            frames: self.frames,
+            inputs: self.inputs.into_iter().map(f).collect(),
+            timestamps: self.timestamps,
+            cursor: self.cursor,
+            delta_time: self.delta_time,
+        }
+    }
+
+    /// Converts the events, leaving out the ones without a result along with their timestamps.
+    pub(crate) fn filter_map<U>(self, mut f: impl FnMut(T) -> Option<U>) -> FrameInput<U> {
+        let timed = self.timestamps.len() == self.inputs.len();
+        let mut inputs = Vec::with_capacity(self.inputs.len());
+        let mut timestamps = Vec::new();
+        for (i, event) in self.inputs.into_iter().enumerate() {
+            if let Some(event) = f(event) {
+                inputs.push(event);
+                if timed {
+                    timestamps.push(self.timestamps[i]);
+                }
+            }
+        }
+        FrameInput {
+            frames: self.frames,
+            inputs,
+            timestamps: if timed { timestamps } else { self.timestamps },
+            cursor: self.cursor,
+            delta_time: self.delta_time,
+        }
+    }
+
+    /// The times since the start of the frame at which the events arrived, if recorded.
+    pub fn timestamps(&self) -> &[f64] {
+        &self.timestamps
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 struct SaveState<T> {
+    /// Optional label shown in the save state list.
+    #[serde(default)]
+    name: Option<String>,
+    /// The checkpoint label provided by the game, see [Tasable::checkpoint_label].
+    #[serde(default)]
+    label: Option<String>,
+    /// The description of the state provided by the game, see [Tasable::describe].
+    #[serde(default)]
+    description: String,
+    /// Whether the state was created automatically (e.g. before a load).
+    #[serde(default)]
+    auto: bool,
     frame: usize,
-    states: Vec<T>,
+    /// The framebuffer size at the moment of saving.
+    #[serde(default)]
+    framebuffer_size: Option<vec2<usize>>,
+    /// The game state at the moment of saving.
+    /// Older saves only have the history, its last snapshot is used instead.
+    #[serde(default)]
+    state: Option<T>,
+    #[serde(flatten)]
+    history: History<T>,
+    #[serde(with = "stable_inputs")]
     inputs: Vec<FrameInput<geng::Event>>,
     pressed_keys: HashSet<geng::Key>,
     pressed_buttons: HashSet<geng::MouseButton>,
+    /// The cursor position in the simulation, restored on load so that a held drag
+    /// continues from the same spot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cursor: Option<vec2<f64>>,
+    /// Auxiliary data of the game, see [Tasable::save_extra].
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    extra: serde_json::Value,
+    /// Whether the state was saved during a replay, so that `history` and `inputs`
+    /// are the ones of the whole replayed run.
+    #[serde(default)]
+    replay: bool,
+    /// The state of the game's RNG, see [Tasable::save_rng].
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "base64_bytes")]
+    rng: Vec<u8>,
+    /// The format of the states, see [Tasable::saved_version].
+    #[serde(default)]
+    version: u32,
+    /// The size (in bytes) of the serialized state, see [Tas::save_states_memory].
+    #[serde(skip)]
+    size: usize,
     // initial_state: T,
-    // state: T,
+}
+
+impl<T> SaveState<T> {
+    /// The game state at the moment of saving.
+    fn game_state(&self) -> &T {
+        self.state
+            .as_ref()
+            .unwrap_or_else(|| self.history.last().unwrap())
+    }
+
+    /// Restores the held keys and buttons, dropping the events queued before the load,
+    /// which belong to the previous state.
+    fn restore_input(
+        &self,
+        pressed_keys: &mut HashSet<geng::Key>,
+        pressed_buttons: &mut HashSet<geng::MouseButton>,
+        queued_inputs: &mut Vec<geng::Event>,
+        queued_timestamps: &mut Vec<f64>,
+    ) {
+        pressed_keys.clone_from(&self.pressed_keys);
+        pressed_buttons.clone_from(&self.pressed_buttons);
+        queued_inputs.clear();
+        queued_timestamps.clear();
+    }
+}
+
+/// A description of a save state, see [Tas::save_states].
+#[derive(Debug, Clone)]
+pub struct SaveStateInfo {
+    /// The index to load the state with, see [Tas::load_state].
+    pub index: usize,
+    /// The frame the state was saved on.
+    pub frame: usize,
+    pub name: Option<String>,
+    /// See [Tasable::checkpoint_label].
+    pub label: Option<String>,
+    /// See [Tasable::describe].
+    pub description: String,
+    /// Whether the state was saved automatically.
+    pub auto: bool,
+    /// The size (in bytes) of the serialized state.
+    pub size: usize,
+}
+
+/// The time (in seconds) between updates of the window title.
+const WINDOW_TITLE_INTERVAL: f64 = 0.25;
+
+/// The maximum number of deleted save states that can be restored.
+const MAX_DELETED_STATES: usize = 10;
+
+/// Formats the time (in seconds) as `m:ss.ss`.
+fn format_time(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{minutes}:{:05.2}", seconds - minutes * 60.0)
+}
+
+/// Formats the size (in bytes) in the largest fitting unit, e.g. `1.5 MB`.
+fn format_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut units = ["KB", "MB", "GB"].as_slice();
+    while size >= 1024.0 && units.len() > 1 {
+        size /= 1024.0;
+        units = &units[1..];
+    }
+    format!("{size:.1} {}", units[0])
+}
+
+/// Parses the time formatted as `m:ss.ss` (or just seconds) into seconds.
+fn parse_time(text: &str) -> Option<f64> {
+    let (minutes, seconds) = match text.trim().split_once(':') {
+        Some((minutes, seconds)) => (minutes.parse::<u64>().ok()?, seconds),
+        None => (0, text.trim()),
+    };
+    let seconds: f64 = seconds.parse().ok()?;
+    (seconds >= 0.0).then_some(minutes as f64 * 60.0 + seconds)
+}
+
+/// Whether the file in the run directory may be a run, and not one of the other TAS files.
+fn is_run_file(path: &std::path::Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str());
+    !matches!(name, Some("savedstates.json" | "macros.json"))
 }
 
 /// Holds the implementation details of the game to be TAS'ed.
+///
+/// Only the events given to the TAS between frames are recorded.
+/// Events fed back into it while a frame is simulated are ignored with a warning.
+/// Input the game reads from elsewhere (e.g. polling a device) is not recorded.
 pub trait Tasable {
     /// A type used for saving and restoring the state of the game.
     type Saved: Clone + Serialize + serde::de::DeserializeOwned;
@@ -84,330 +573,2662 @@ pub trait Tasable {
 
     /// Restore a previously saved state.
     fn load(&mut self, state: Self::Saved);
+
+    /// Create a copy of the game to be drawn in between the `previous` state and the current one,
+    /// where `alpha` goes from 0 (previous) to 1 (current).
+    /// Used to smooth out the motion when frames are simulated slower than they are drawn.
+    /// Returning `None` (the default) draws the current state as is.
+    fn interpolate(&self, previous: &Self::Saved, alpha: f64) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        #![allow(unused_variables)]
+        None
+    }
+
+    /// Called when a state or a run is loaded with the framebuffer size it was recorded with,
+    /// so that games depending on it can simulate the same way.
+    fn restore_framebuffer_size(&mut self, size: vec2<usize>) {
+        #![allow(unused_variables)]
+    }
+
+    /// A label for the current game state (e.g. the level name) to tag save states with,
+    /// so they can later be loaded with [Tas::load_state_by_label].
+    fn checkpoint_label(&self) -> Option<String> {
+        None
+    }
+
+    /// Serializes the state in a custom (e.g. compressed) format to store it in files.
+    /// Returning `None` (the default) stores it with its [Serialize] implementation.
+    fn serialize_saved(state: &Self::Saved) -> Option<Vec<u8>> {
+        #![allow(unused_variables)]
+        None
+    }
+
+    /// The version of the [Tasable::Saved] format, stored in save states and runs.
+    /// Increase it when the meaning of the saved data changes,
+    /// and convert the older states in [Tasable::migrate].
+    fn saved_version() -> u32 {
+        0
+    }
+
+    /// Converts a state saved with another [Tasable::saved_version] to the current format.
+    /// Called for all the states of a loaded run or save state with a different version,
+    /// once they are deserialized. Returns the state as is by default.
+    fn migrate(&self, state: Self::Saved, version: u32) -> Self::Saved {
+        #![allow(unused_variables)]
+        state
+    }
+
+    /// Restores a state serialized with [Tasable::serialize_saved].
+    /// Returning `None` (the default) fails to load it.
+    fn deserialize_saved(bytes: &[u8]) -> Option<Self::Saved> {
+        #![allow(unused_variables)]
+        None
+    }
+
+    /// A short human-readable description of the current game state (e.g. level and score),
+    /// shown next to save states. Empty (the default) shows nothing.
+    fn describe(&self) -> String {
+        String::new()
+    }
+
+    /// Auxiliary data (e.g. statistics or debug flags) to store in save states,
+    /// which is not needed to reproduce the simulation, unlike [Tasable::Saved].
+    /// `Null` (the default) stores nothing.
+    fn save_extra(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+
+    /// Restores the data saved with [Tasable::save_extra] when a save state is loaded.
+    fn load_extra(&mut self, extra: serde_json::Value) {
+        #![allow(unused_variables)]
+    }
+
+    /// The internal state of an RNG kept outside of [Tasable::Saved], stored in save states
+    /// and runs so that loading them continues the same random sequence.
+    /// Empty (the default) stores nothing.
+    ///
+    /// Snapshots in between do not include it, so seeking only restores it
+    /// when simulating from the start. Keeping the RNG in [Tasable::Saved] avoids that.
+    fn save_rng(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores the RNG state saved with [Tasable::save_rng].
+    fn load_rng(&mut self, bytes: &[u8]) {
+        #![allow(unused_variables)]
+    }
+
+    /// Checked after every recorded frame to save a state automatically whenever it changes
+    /// to `true`, e.g. when entering a new room. Such states count towards
+    /// [TasConfig::max_auto_snapshots].
+    fn should_autosave(&self) -> bool {
+        false
+    }
+
+    /// Checked after every recorded frame to tell whether the run is complete (e.g. the goal
+    /// has been reached). Once it returns `true`, the simulation is paused and the run is saved
+    /// into [TasConfig::finished_runs_dir], if set.
+    fn is_finished(&self) -> bool {
+        false
+    }
+
+    /// Called after every simulated frame with its index,
+    /// e.g. to update a frame-synced debug display.
+    fn on_frame(&mut self, frame: usize) {
+        #![allow(unused_variables)]
+    }
+
+    /// Polled after every frame to tell whether the game has reloaded its own state during it
+    /// (e.g. restarting from a checkpoint after the player dies), and should return `true` once
+    /// for each such reload. The TAS then stops interpolating across the reload,
+    /// and snapshots the reloaded state, so that seeking to that frame restores it.
+    ///
+    /// Reloads only replay correctly if they are deterministic, i.e. caused by the recorded inputs
+    /// and not by something outside of the game state (like reading a file that may change).
+    /// Otherwise replays desync on the frame after the reload, see [TasConfig::desync_policy].
+    fn take_state_reload(&mut self) -> bool {
+        false
+    }
+
+    /// Decide how an incoming event is recorded.
+    /// Returning `None` drops the event, otherwise the returned event is recorded instead.
+    fn filter_event(&self, event: &geng::Event) -> Option<geng::Event> {
+        Some(event.clone())
+    }
 }
 
 impl<T: geng::State + Tasable> Tas<T> {
     pub fn new(game: T, geng: &Geng) -> Self {
+        Self::with_config(game, geng, default())
+    }
+
+    pub fn with_config(game: T, geng: &Geng, config: TasConfig) -> Self {
+        let mut tas = Self::build(game, Some(geng.clone()), config);
+        tas.load_savestates().expect("Failed to load saved states");
+        if let Err(err) = tas.load_macros() {
+            log_error!("Failed to load macros: {err}");
+        }
+        tas.refresh_run_files();
+        if let Err(err) = tas.recover_log() {
+            log_error!("Failed to recover the recording log: {err}");
+        }
+        if tas.config.load_recent_run && tas.inputs.is_empty() {
+            if let Err(err) = tas.load_most_recent_run() {
+                log_error!("Failed to load the most recent run: {err}");
+            }
+        }
+        tas
+    }
+
+    /// Creates the TAS without a window, leaving out everything stored on the disk.
+    #[cfg(test)]
+    pub(crate) fn headless(game: T, config: TasConfig) -> Self {
+        Self::build(game, None, config)
+    }
+
+    /// Creates the TAS without loading anything from the disk.
+    fn build(mut game: T, geng: Option<Geng>, config: TasConfig) -> Self {
+        if let Some(size) = config.lock_framebuffer_size {
+            game.restore_framebuffer_size(size);
+        }
+        let history = History::new(config.snapshot_interval, game.save());
+        let paused = config.start_paused;
+        let time_scale = config.default_time_scale;
+        let max_time_scale = config.max_time_scale;
+        let defer_initial_state = config.defer_initial_state;
+        let slow_time_scale = config.slow_time_scale;
+        let ui_disabled = config.disable_ui;
         let mut tas = Self {
-            geng: geng.clone(),
+            geng,
+            config,
             framebuffer_size: vec2(1, 1),
             show_ui: true,
-            time_scale: 1.0,
-            paused: true,
-            auto_paused: false,
+            ui_disabled,
+            ui_peeking: false,
+            time_scale: time_scale.clamp(0.0, max_time_scale),
+            slow_time_scale,
+            paused,
+            reversing: false,
+            menu_open: false,
             fixed_delta_time: 1.0,
+            engine_delta_time: 1.0,
             saved_states: Vec::new(),
+            states_dirty: false,
+            states_flush_timer: 0.0,
+            title_timer: 0.0,
+            deleted_states: VecDeque::new(),
             selected_state: 0,
+            loaded_state: None,
             frame: 0,
             inputs: Vec::new(),
-            states: vec![game.save()],
+            history,
             save_file: "tas.json".to_string(),
+            run_files: Vec::new(),
+            text_focused: false,
+            console_open: false,
+            show_help: false,
+            show_log: false,
+            show_input_overlay: true,
+            diff_base: None,
+            state_diff: Vec::new(),
+            goto_input: String::new(),
+            goto_time_input: String::new(),
+            console_input: String::new(),
+            console_log: VecDeque::new(),
             replay: None,
             // initial_state: game.save(),
             game,
-            acc_delta_time: 0.0,
+            clock: FrameClock::default(),
+            simulation: SimulationGuard::default(),
             queued_inputs: Vec::new(),
+            queued_timestamps: Vec::new(),
+            frame_time: 0.0,
+            cursor_override: None,
+            cursor_position: None,
+            frame_delta_time: None,
             pressed_keys: HashSet::new(),
             pressed_buttons: HashSet::new(),
+            window_sync_supported: true,
+            synced_keys: HashSet::new(),
+            synced_buttons: HashSet::new(),
+            external_states: None,
+            continue_after_replay: false,
+            playlist: VecDeque::new(),
+            toasts: VecDeque::new(),
+            comparison: None,
+            reference: None,
+            reference_match: None,
+            desync_frame: None,
+            render_hashes: BTreeMap::new(),
+            frame_intervals: Vec::new(),
+            pacing_time: 0.0,
+            resimulating: false,
+            last_hashed_frame: None,
+            comments: Vec::new(),
+            comment_input: String::new(),
+            goal_frame: None,
+            finished: false,
+            last_autosave_condition: false,
+            initial_rng: Vec::new(),
+            looped_range: None,
+            previous_state: None,
+            log_dirty: true,
+            frame_callback: None,
+            breakpoints: Vec::new(),
+            input_breakpoint: None,
+            initial_state_pending: defer_initial_state,
+            preview_frame: None,
+            preview: None,
+            rerecords: 0,
+            recording_slot: "main".to_owned(),
+            recording_slots: HashMap::new(),
+            rerecord_reference: None,
+            burst_frames: 0,
+            lag_frames: 0,
+            lagging: false,
+            macros: HashMap::new(),
+            selected_macro: None,
+            macro_recording: None,
+            pending_macro: VecDeque::new(),
         };
-        tas.load_savestates().expect("Failed to load saved states");
+        tas.initial_rng = tas.game.save_rng();
         tas
     }
 
+    /// The engine, which is only missing in tests.
+    fn geng(&self) -> &Geng {
+        self.geng.as_ref().expect("The TAS runs without a window")
+    }
+
+    /// The cursor position in the window, or the simulated one without a window.
+    fn window_cursor(&self) -> vec2<f64> {
+        match &self.geng {
+            Some(geng) => geng.window().cursor_position(),
+            None => self.cursor_position.unwrap_or(vec2(0.0, 0.0)),
+        }
+    }
+
+    /// Moves the cursor in the window, if there is one.
+    fn set_window_cursor(&self, position: vec2<f64>) {
+        if let Some(geng) = &self.geng {
+            geng.window().set_cursor_position(position);
+        }
+    }
+
+    /// Drives the game from states received over the channel instead of simulating it.
+    /// Each frame the next available state is loaded in place of `update`/`fixed_update`.
+    ///
+    /// Inputs are still recorded in this mode, but they are meaningless,
+    /// since they do not affect the game state.
+    pub fn set_external_state_source(&mut self, rx: std::sync::mpsc::Receiver<T::Saved>) {
+        self.external_states = Some(rx);
+    }
+
+    /// Returns to simulating the game normally.
+    pub fn clear_external_state_source(&mut self) {
+        self.external_states = None;
+    }
+
+    /// Registers a callback that is invoked after every simulated frame
+    /// with the index of that frame and the resulting game state.
+    pub fn set_frame_callback(&mut self, callback: impl FnMut(usize, &T) + 'static) {
+        self.frame_callback = Some(Box::new(callback));
+    }
+
+    pub fn clear_frame_callback(&mut self) {
+        self.frame_callback = None;
+    }
+
+    /// Adds a condition checked after every simulated frame,
+    /// pausing the simulation once it holds for the game.
+    /// It is evaluated on every frame, so it should be cheap.
+    pub fn add_conditional_breakpoint(&mut self, predicate: Box<dyn Fn(&T) -> bool>) {
+        self.breakpoints.push(predicate);
+    }
+
+    pub fn clear_conditional_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Pauses the replay right before the next frame on which the event starts being applied,
+    /// e.g. just before the next jump. Returns that frame, if the event occurs again.
+    pub fn break_before_input(&mut self, event: &geng::Event) -> Option<usize> {
+        let replay = self.replay.as_ref()?;
+        let mut start = 0;
+        let mut target = None;
+        for input in &replay.inputs {
+            if start > self.frame && input.inputs.contains(event) {
+                target = Some(start);
+                break;
+            }
+            start += input.frames;
+        }
+        self.input_breakpoint = target;
+        target
+    }
+
+    /// Sets the frame the run should end before, e.g. for an optimization target,
+    /// to show the current frame against it. The goal is saved with the run.
+    pub fn set_goal_frame(&mut self, goal: Option<usize>) {
+        self.goal_frame = goal;
+    }
+
+    pub fn goal_frame(&self) -> Option<usize> {
+        self.goal_frame
+    }
+
+    pub fn config(&self) -> &TasConfig {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut TasConfig {
+        &mut self.config
+    }
+
+    /// The state the current recording or replay started from.
+    fn initial_state(&self) -> Option<&T::Saved> {
+        match &self.replay {
+            Some(replay) => replay.history.first(),
+            None => self.history.first(),
+        }
+    }
+
+    /// Starts a fresh recording from the initial state of the current one.
+    /// Save states and settings are kept.
+    pub fn restart(&mut self) {
+        let Some(initial_state) = self.initial_state().cloned() else {
+            return;
+        };
+        let rng = self.initial_rng.clone();
+        self.set_initial_state(initial_state);
+        self.initial_rng = rng;
+        self.load_initial_rng();
+    }
+
+    /// Restores the RNG state the run started with, see [Tasable::save_rng].
+    fn load_initial_rng(&mut self) {
+        if !self.initial_rng.is_empty() {
+            self.game.load_rng(&self.initial_rng);
+        }
+    }
+
+    /// Starts a fresh recording from the given state, e.g. a specific level
+    /// instead of the state the game was in when the TAS was created.
+    /// Save states and settings are kept.
+    pub fn set_initial_state(&mut self, initial_state: T::Saved) {
+        self.initial_state_pending = false;
+        self.replay = None;
+        self.continue_after_replay = false;
+        self.game.load(initial_state.clone());
+        self.initial_rng = self.game.save_rng();
+        self.history = History::new(self.config.snapshot_interval, initial_state);
+        self.frame = 0;
+        self.inputs.clear();
+        self.render_hashes.clear();
+        self.frame_intervals.clear();
+        self.pacing_time = 0.0;
+        self.queued_inputs.clear();
+        self.queued_timestamps.clear();
+        self.pending_macro.clear();
+        self.pressed_keys.clear();
+        self.pressed_buttons.clear();
+        self.clock.reset();
+        self.log_dirty = true;
+    }
+
     /// Saves the current game state.
     fn save_state(&mut self) {
-        self.saved_states.push(SaveState {
+        self.push_state(None, false);
+    }
+
+    /// Removes the save states of the previous run, see [TasConfig::keep_states_on_load].
+    fn clear_states_for_new_run(&mut self) {
+        if self.saved_states.is_empty() {
+            return;
+        }
+        self.saved_states.clear();
+        self.deleted_states.clear();
+        self.selected_state = 0;
+        self.loaded_state = None;
+        self.states_dirty = true;
+    }
+
+    /// Saves the current state as an automatic snapshot before it gets overwritten by a load.
+    fn snapshot_before_load(&mut self) {
+        if !self.config.snapshot_before_load || self.inputs.is_empty() {
+            return;
+        }
+        self.push_auto_snapshot("auto before load".to_string());
+    }
+
+    /// Saves the current state as an automatic snapshot,
+    /// removing the oldest ones to stay within [TasConfig::max_auto_snapshots].
+    fn push_auto_snapshot(&mut self, name: String) {
+        // Remove the oldest automatic snapshots to stay within the limit
+        let max = self.config.max_auto_snapshots.saturating_sub(1);
+        while self.saved_states.iter().filter(|state| state.auto).count() > max {
+            let Some(oldest) = self.saved_states.iter().position(|state| state.auto) else {
+                break;
+            };
+            self.remove_state_at(oldest);
+        }
+        self.selected_state = self
+            .selected_state
+            .min(self.saved_states.len().saturating_sub(1));
+
+        if self.config.max_auto_snapshots > 0 {
+            self.push_state(Some(name), true);
+        }
+    }
+
+    fn push_state(&mut self, name: Option<String>, auto: bool) {
+        // Make sure the state can be written to disk before keeping it in memory
+        let state = self.game.save();
+        if let Err(err) = StoredState::store::<T>(&state) {
+            self.notify_error(format!(
+                "Failed to save state, it cannot be serialized: {err}"
+            ));
+            return;
+        }
+
+        // Name unnamed states by the time into the run, e.g. "0:12.34 #3"
+        let name = name.unwrap_or_else(|| {
+            let time = self.frame as f64 * self.fixed_delta_time;
+            format!("{} #{}", format_time(time), self.saved_states.len())
+        });
+
+        // During a replay, keep the whole run to be able to resume it
+        let (history, inputs) = match &self.replay {
+            Some(replay) => (replay.history.clone(), replay.inputs.clone()),
+            None => (self.history.clone(), self.inputs.clone()),
+        };
+
+        let mut saved = SaveState {
+            name: Some(name),
+            label: self.game.checkpoint_label(),
+            description: self.game.describe(),
+            auto,
             frame: self.frame,
-            states: self.states.clone(),
-            inputs: self.inputs.clone(),
+            framebuffer_size: Some(self.recorded_framebuffer_size()),
+            state: Some(state),
+            history,
+            inputs,
             // initial_state: self.initial_state.clone(),
             // state: self.game.save(),
             pressed_keys: self.pressed_keys.clone(),
             pressed_buttons: self.pressed_buttons.clone(),
-        });
-        if let Err(err) = self.save_savestates() {
-            log::error!("Failed to save states: {err}");
+            cursor: self.cursor_position,
+            extra: self.game.save_extra(),
+            replay: self.replay.is_some(),
+            rng: self.game.save_rng(),
+            version: T::saved_version(),
+            size: 0,
+        };
+        saved.size = stored_size::<T>(&saved);
+        self.saved_states.push(saved);
+        self.states_dirty = true;
+        if !auto {
+            self.notify(format!("State saved (#{})", self.saved_states.len() - 1));
+        }
+    }
+
+    /// Removes the save state, keeping track of the loaded one.
+    fn remove_state_at(&mut self, index: usize) -> SaveState<T::Saved> {
+        self.loaded_state = match self.loaded_state {
+            Some(loaded) if loaded == index => None,
+            Some(loaded) if loaded > index => Some(loaded - 1),
+            loaded => loaded,
+        };
+        self.saved_states.remove(index)
+    }
+
+    /// Deletes the save state, keeping it around to be restored with [Self::undo_delete_state].
+    fn delete_state(&mut self, index: usize) {
+        if index >= self.saved_states.len() {
+            return;
+        }
+        let state = self.remove_state_at(index);
+        if self.deleted_states.len() >= MAX_DELETED_STATES {
+            self.deleted_states.pop_front();
+        }
+        self.deleted_states.push_back((index, state));
+        self.diff_base = None;
+        self.selected_state = self
+            .selected_state
+            .min(self.saved_states.len().saturating_sub(1));
+        self.states_dirty = true;
+    }
+
+    /// Restores the most recently deleted save state to its former position.
+    fn undo_delete_state(&mut self) {
+        let Some((index, state)) = self.deleted_states.pop_back() else {
+            return;
+        };
+        let index = index.min(self.saved_states.len());
+        self.saved_states.insert(index, state);
+        if let Some(loaded) = &mut self.loaded_state {
+            if *loaded >= index {
+                *loaded += 1;
+            }
         }
+        self.diff_base = None;
+        self.states_dirty = true;
     }
 
     /// Attempts to load the saved state by index.
     /// If such a state is not found, nothing happens.
-    fn load_state(&mut self, index: usize) {
+    pub fn load_state(&mut self, index: usize) {
         // Stop replay
         self.replay.take();
+        self.continue_after_replay = false;
 
         // Get the state by index
         if let Some(state) = self.saved_states.get(index) {
             let state = state.clone();
-            self.game.load(state.states.last().unwrap().clone());
+            // Set before the snapshot, which may remove states before this one
+            self.loaded_state = Some(index);
+            self.snapshot_before_load();
+            self.game.load(state.game_state().clone());
+            if !state.rng.is_empty() {
+                self.game.load_rng(&state.rng);
+            }
+            self.restore_framebuffer_size(state.framebuffer_size);
             self.frame = state.frame;
-            self.states = state.states;
-            self.inputs = state.inputs;
-            self.pressed_keys = state.pressed_keys;
-            self.pressed_buttons = state.pressed_buttons;
+            state.restore_input(
+                &mut self.pressed_keys,
+                &mut self.pressed_buttons,
+                &mut self.queued_inputs,
+                &mut self.queued_timestamps,
+            );
+            self.restore_cursor(state.cursor);
+            self.game.load_extra(state.extra);
+            self.log_dirty = true;
+            if state.replay && self.config.resume_replay_from_states {
+                // Continue the replay the state was saved in
+                self.inputs.clear();
+                self.history.clear();
+                self.rerecord_reference = None;
+                let mut replay = Replay::new(state.history, state.inputs);
+                replay.frame = state.frame;
+                (replay.input, replay.next_input) = input_position(&replay.inputs, state.frame);
+                self.replay = Some(replay);
+                return;
+            }
+            self.history = state.history;
+            self.rerecord_reference = Some(std::mem::replace(&mut self.inputs, state.inputs));
+            if state.replay {
+                // Record from the frame the state was saved on
+                truncate_inputs(&mut self.inputs, self.frame);
+                self.history.truncate(self.frame);
+            }
             // self.initial_state = state.initial_state;
             // self.game.load(state.state);
         }
     }
 
-    /// Saves the run in a file.
+    /// Lists the save states, e.g. to show them in a custom UI.
+    pub fn save_states(&self) -> impl Iterator<Item = SaveStateInfo> + '_ {
+        self.saved_states
+            .iter()
+            .enumerate()
+            .map(|(index, state)| SaveStateInfo {
+                index,
+                frame: state.frame,
+                name: state.name.clone(),
+                label: state.label.clone(),
+                description: state.description.clone(),
+                auto: state.auto,
+                size: state.size,
+            })
+    }
+
+    /// The index of the last loaded save state, until new inputs are recorded after loading it.
+    pub fn loaded_state(&self) -> Option<usize> {
+        self.loaded_state
+    }
+
+    /// An estimate of the memory (in bytes) taken by all save states,
+    /// based on their serialized size.
+    pub fn save_states_memory(&self) -> usize {
+        self.saved_states.iter().map(|state| state.size).sum()
+    }
+
+    /// Loads the most recent save state tagged with the given checkpoint label.
+    /// Returns whether such a state was found.
+    pub fn load_state_by_label(&mut self, label: &str) -> bool {
+        let Some(index) = self
+            .saved_states
+            .iter()
+            .rposition(|state| state.label.as_deref() == Some(label))
+        else {
+            return false;
+        };
+        self.selected_state = index;
+        self.load_state(index);
+        true
+    }
+
+    /// The framebuffer size the game is simulated with, see [TasConfig::lock_framebuffer_size].
+    fn recorded_framebuffer_size(&self) -> vec2<usize> {
+        self.config
+            .lock_framebuffer_size
+            .unwrap_or(self.framebuffer_size)
+    }
+
+    /// Lets the game know the framebuffer size the loaded state was recorded with.
+    fn restore_framebuffer_size(&mut self, size: Option<vec2<usize>>) {
+        if let Some(size) = size {
+            let current = self.recorded_framebuffer_size();
+            if size != current {
+                log_info!(
+                    "Loaded state was recorded with framebuffer size {size:?}, \
+                    the current one is {current:?}"
+                );
+            }
+            match self.config.lock_framebuffer_size {
+                // The size stays locked
+                Some(locked) => self.game.restore_framebuffer_size(locked),
+                None => self.game.restore_framebuffer_size(size),
+            }
+        }
+    }
+
+    /// Gives mutable access to the game in between frames, e.g. to set up a test scenario.
+    ///
+    /// Like [Self::load_state_only], changes made this way are not recorded
+    /// and break the invariant that the recorded inputs reproduce the current game state,
+    /// so it is meant for testing, not for making runs.
+    pub fn with_game<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.game)
+    }
+
+    /// Loads only the game state (and the pressed keys and buttons) from the save state,
+    /// keeping the current recording: its frame, inputs, and history.
+    ///
+    /// This breaks the invariant that the recorded inputs reproduce the current game state,
+    /// so it is meant only for experimentation.
+    pub fn load_state_only(&mut self, index: usize) {
+        let Some(state) = self.saved_states.get(index) else {
+            return;
+        };
+        self.pressed_keys = state.pressed_keys.clone();
+        self.pressed_buttons = state.pressed_buttons.clone();
+        let extra = state.extra.clone();
+        let rng = state.rng.clone();
+        self.game.load(state.game_state().clone());
+        if !rng.is_empty() {
+            self.game.load_rng(&rng);
+        }
+        self.game.load_extra(extra);
+    }
+
+    /// Saves the run in a file, creating its directory if needed.
     fn save_run(
         &self,
         path: impl AsRef<std::path::Path>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let file = std::fs::File::create(path)?;
-        let writer = std::io::BufWriter::new(file);
-        let saved = SavedTas::<T::Saved> {
-            // initial_state: self.initial_state.clone(),
-            states: self.states.clone(),
-            inputs: self.inputs.clone(),
-        };
-        serde_json::to_writer_pretty(writer, &saved)?;
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.save_run_to_bytes()?)?;
         Ok(())
     }
 
-    /// Loads the run from the file.
-    fn load_run(
-        &mut self,
-        path: impl AsRef<std::path::Path>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let file = std::fs::File::open(path)?;
-        let reader = std::io::BufReader::new(file);
-        let saved: SavedTas<T::Saved> = serde_json::from_reader(reader)?;
+    /// Saves a copy of the current run to `{save_file}.bak.{timestamp}` (in seconds since
+    /// the Unix epoch), e.g. before a risky edit, leaving the save file as is.
+    /// Returns the path of the copy.
+    pub fn backup_run(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        let path = format!("{}.bak.{timestamp}", self.save_file);
+        self.save_run(&path)?;
+        Ok(path)
+    }
 
-        self.game.load(saved.states.first().unwrap().clone());
-        self.frame = 0;
-        self.queued_inputs.clear();
-        self.inputs.clear();
-        self.states.clear();
-        self.pressed_keys.clear();
-        self.pressed_buttons.clear();
-        self.replay = Some(Replay {
-            frame: 0,
-            states: saved.states,
-            input: 0,
-            next_input: saved.inputs.first().map(|input| input.frames).unwrap_or(0),
-            inputs: saved.inputs,
-        });
-        Ok(())
+    /// Hashes the initial state and the inputs of the current run,
+    /// so that identical runs (e.g. saved under different names) can be told apart.
+    /// The hash of the state is only stable if it serializes deterministically
+    /// (e.g. without hash maps).
+    pub fn run_hash(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        run_hash(&self.initial_state(), self.inputs())
     }
 
-    fn save_savestates(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let file = std::fs::File::create("savedstates.json")?;
-        let writer = std::io::BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &self.saved_states)?;
-        Ok(())
+    /// Serializes the run into memory, e.g. for platforms without a filesystem.
+    pub fn save_run_to_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut saved = self.saved_run();
+        if self.config.relative_runs {
+            self.reference_base_state(&mut saved)?;
+        }
+        write_run::<T>(&saved, self.config.compact_json)
     }
 
-    fn load_savestates(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let Ok(file) = std::fs::File::open("savedstates.json") else {
-            log::warn!("Failed to open savedstates.json");
-            self.saved_states = default();
+    /// Replaces the initial state of the run with the hash of the save state
+    /// with the same game state, if there is one, see [TasConfig::relative_runs].
+    fn reference_base_state(
+        &self,
+        saved: &mut SavedTas<T::Saved>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(initial_state) = saved.history.first() else {
             return Ok(());
         };
-        let reader = std::io::BufReader::new(file);
-        self.saved_states = serde_json::from_reader(reader)?;
-        Ok(())
-    }
+        let hash = state_hash(initial_state)?;
+        let found = self
+            .saved_states
+            .iter()
+            .any(|state| state_hash(state.game_state()).map_or(false, |other| other == hash));
+        if found {
+            saved.history.states.remove(0);
+            saved.base_state = Some(hash);
+        }
+        Ok(())
+    }
+
+    /// Puts the referenced save state back as the initial state of the run,
+    /// see [TasConfig::relative_runs].
+    pub(crate) fn resolve_base_state(
+        &self,
+        saved: &mut SavedTas<T::Saved>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(hash) = saved.base_state.take() else {
+            return Ok(());
+        };
+        let state = self
+            .saved_states
+            .iter()
+            .map(|state| state.game_state())
+            .find(|state| state_hash(*state).map_or(false, |other| other == hash))
+            .ok_or_else(|| {
+                format!(
+                    "The run starts from the save state {hash:016x}, \
+                    which is not among the save states"
+                )
+            })?;
+        saved.history.states.insert(0, state.clone());
+        Ok(())
+    }
+
+    /// Collects the current recording the way it is saved.
+    pub(crate) fn saved_run(&self) -> SavedTas<T::Saved> {
+        SavedTas::<T::Saved> {
+            header: Some(RunHeader::new(self.fixed_delta_time)),
+            fixed_delta_time: Some(self.fixed_delta_time),
+            framebuffer_size: Some(self.recorded_framebuffer_size()),
+            rerecords: self.rerecords,
+            total_frames: Some(total_frames(&self.inputs)),
+            input_count: Some(self.inputs.len()),
+            render_hashes: self
+                .render_hashes
+                .range(..=self.frame)
+                .map(|(&frame, &hash)| (frame, hash))
+                .collect(),
+            frame_intervals: self.frame_intervals[..self.frame_intervals.len().min(self.frame)]
+                .to_vec(),
+            comments: self.comments.clone(),
+            goal_frame: self.goal_frame,
+            rng: self.initial_rng.clone(),
+            version: T::saved_version(),
+            base_state: None,
+            // initial_state: self.initial_state.clone(),
+            history: self.history.clone(),
+            inputs: self.inputs.clone(),
+        }
+    }
+
+    /// Loads the run from the file.
+    fn load_run(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = std::fs::read(path)?;
+        self.load_run_from_bytes(&bytes)
+    }
+
+    /// Loads the run serialized with [Self::save_run_to_bytes] and starts replaying it.
+    pub fn load_run_from_bytes(&mut self, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut saved = read_run::<T>(bytes)?;
+        migrate_run(&self.game, &mut saved);
+        self.resolve_base_state(&mut saved)?;
+
+        if let Some(recorded) = saved.fixed_delta_time {
+            if (recorded - self.fixed_delta_time).abs() > 1e-9 {
+                let message = format!(
+                    "The run was recorded with fixed delta time {recorded}, \
+                    but the current one is {}, the replay will likely desync",
+                    self.fixed_delta_time
+                );
+                if self.config.refuse_delta_time_mismatch {
+                    return Err(message.into());
+                }
+                log_warn!("{message}");
+            }
+        }
+
+        if !self.config.keep_states_on_load {
+            self.clear_states_for_new_run();
+        }
+        self.snapshot_before_load();
+        self.game.load(saved.history.first().unwrap().clone());
+        self.restore_framebuffer_size(saved.framebuffer_size);
+        self.rerecords = saved.rerecords;
+        self.rerecord_reference = None;
+        self.frame = 0;
+        self.queued_inputs.clear();
+        self.queued_timestamps.clear();
+        self.inputs.clear();
+        self.history.clear();
+        self.pressed_keys.clear();
+        self.pressed_buttons.clear();
+        self.continue_after_replay = false;
+        self.looped_range = None;
+        self.render_hashes.clear();
+        self.frame_intervals.clear();
+        self.comments = saved.comments;
+        self.loaded_state = None;
+        self.goal_frame = saved.goal_frame;
+        self.initial_rng = saved.rng;
+        self.load_initial_rng();
+        let mut replay = Replay::new(saved.history, saved.inputs);
+        replay.render_hashes = saved.render_hashes;
+        replay.frame_intervals = saved.frame_intervals;
+        self.replay = Some(replay);
+        if self.config.pause_on_load {
+            self.paused = true;
+        }
+        Ok(())
+    }
+
+    /// Loads the run and sets the time scale so that replaying it takes the given
+    /// real-time duration (in seconds), e.g. to fit it into a video of a fixed length.
+    pub fn replay_over_duration(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        seconds: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if seconds <= 0.0 {
+            return Err(format!("Invalid replay duration: {seconds}").into());
+        }
+        self.load_run(path)?;
+        let frames = self.replay.as_ref().map_or(0, |replay| replay.total_frames);
+        let time_scale = frames as f64 * self.fixed_delta_time / seconds;
+        if time_scale > self.config.max_time_scale {
+            log_warn!("The replay is too long to play over {seconds} seconds");
+        }
+        self.set_time_scale(time_scale);
+        self.paused = false;
+        Ok(())
+    }
+
+    /// Replays the current recording from its initial state,
+    /// then pauses at its end to continue recording from there.
+    pub fn watch_recording(&mut self) {
+        if self.replay.is_some() {
+            return;
+        }
+        let Some(initial_state) = self.initial_state().cloned() else {
+            return;
+        };
+        self.game.load(initial_state);
+        self.load_initial_rng();
+        self.pressed_keys.clear();
+        self.pressed_buttons.clear();
+        self.queued_inputs.clear();
+        self.queued_timestamps.clear();
+        self.frame = 0;
+        let inputs = std::mem::take(&mut self.inputs);
+        let history = std::mem::replace(
+            &mut self.history,
+            History::new(self.config.snapshot_interval, self.game.save()),
+        );
+        let mut replay = Replay::new(history, inputs);
+        replay.render_hashes = std::mem::take(&mut self.render_hashes);
+        replay.frame_intervals = std::mem::take(&mut self.frame_intervals);
+        self.replay = Some(replay);
+        self.continue_after_replay = true;
+        self.paused = false;
+    }
+
+    /// Turns the replay into a recording at the current frame, so that a correction can be made,
+    /// dropping the replayed inputs past the current frame. The simulation is paused.
+    ///
+    /// Live inputs are ignored during replays, this is the way to record over them.
+    pub fn take_over_replay(&mut self) {
+        self.continue_after_replay = false;
+        let Some(replay) = self.replay.take() else {
+            return;
+        };
+        self.inputs = replay.inputs;
+        truncate_inputs(&mut self.inputs, self.frame);
+        self.history = replay.history;
+        self.history.truncate(self.frame);
+        self.render_hashes = replay.render_hashes;
+        self.frame_intervals = replay.frame_intervals;
+        self.frame_intervals.truncate(self.frame);
+        self.pacing_time = 0.0;
+        self.log_dirty = true;
+        self.paused = true;
+        self.notify(format!("Recording from frame {}", self.frame));
+    }
+
+    /// Makes the current frame the end of the run, discarding the inputs after it
+    /// along with the save states saved after it. Replays are taken over at the current frame.
+    pub fn truncate_at_current(&mut self) {
+        if self.replay.is_some() {
+            self.take_over_replay();
+        }
+        truncate_inputs(&mut self.inputs, self.frame);
+        self.history.truncate(self.frame);
+        self.render_hashes.retain(|&frame, _| frame <= self.frame);
+        self.frame_intervals.truncate(self.frame);
+        self.rerecord_reference = None;
+        self.log_dirty = true;
+
+        let mut index = 0;
+        while index < self.saved_states.len() {
+            if self.saved_states[index].frame > self.frame {
+                self.remove_state_at(index);
+                self.states_dirty = true;
+            } else {
+                index += 1;
+            }
+        }
+        self.selected_state = self
+            .selected_state
+            .min(self.saved_states.len().saturating_sub(1));
+        self.notify(format!("Cut the run at frame {}", self.frame));
+    }
+
+    /// Stops the replay and starts a new recording with the current state as its initial state,
+    /// unlike [Self::take_over_replay], which keeps the replayed inputs before the current frame.
+    /// The keys and buttons held at the moment are pressed again on the first frame.
+    pub fn fork_replay(&mut self) {
+        if self.replay.is_none() {
+            return;
+        }
+        let frame = self.frame;
+        let keys = std::mem::take(&mut self.pressed_keys);
+        let buttons = std::mem::take(&mut self.pressed_buttons);
+        self.set_initial_state(self.game.save());
+        let position = self.window_cursor();
+        for key in keys {
+            self.queue_event(geng::Event::KeyDown { key });
+        }
+        for button in buttons {
+            self.queue_event(geng::Event::MouseDown { position, button });
+        }
+        self.paused = true;
+        self.notify(format!("Forked a new run from frame {frame}"));
+    }
+
+    /// Queues the runs to be replayed one after another.
+    /// Unless a replay is already going, the first one is started immediately.
+    pub fn set_playlist(&mut self, paths: Vec<std::path::PathBuf>) {
+        self.playlist = paths.into();
+        if self.replay.is_none() {
+            self.play_next_run();
+        }
+    }
+
+    /// Starts replaying the next run in the playlist.
+    /// Returns whether a run was started.
+    fn play_next_run(&mut self) -> bool {
+        while let Some(path) = self.playlist.pop_front() {
+            match self.load_run(&path) {
+                Ok(()) => {
+                    self.notify(format!("Playing {}", path.display()));
+                    return true;
+                }
+                Err(err) => {
+                    self.notify_error(format!("Failed to load run {}: {err}", path.display()))
+                }
+            }
+        }
+        false
+    }
+
+    /// Finds the run files in the directory of the save file.
+    fn refresh_run_files(&mut self) {
+        let dir = self.run_dir();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            log_warn!("Failed to read the run directory {dir:?}");
+            self.run_files.clear();
+            return;
+        };
+        self.run_files = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+            .filter(|path| is_run_file(path))
+            .map(|path| {
+                let path = path.strip_prefix(".").unwrap_or(&path);
+                path.to_string_lossy().into_owned()
+            })
+            .collect();
+        self.run_files.sort();
+    }
+
+    /// The directory containing the save file, where the runs are looked for.
+    fn run_dir(&self) -> &std::path::Path {
+        std::path::Path::new(&self.save_file)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(std::path::Path::new("."))
+    }
+
+    /// Loads the most recently modified run in the directory of the save file:
+    /// either a run (`.json`) or exported inputs (`.bin`).
+    /// Returns whether a run was found.
+    pub fn load_most_recent_run(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+        let newest = std::fs::read_dir(self.run_dir())?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let path = entry.path();
+                let ext = path.extension().and_then(|ext| ext.to_str());
+                matches!(ext, Some("json" | "bin")) && is_run_file(&path)
+            })
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .max_by_key(|(modified, _)| *modified);
+        let Some((_, path)) = newest else {
+            return Ok(false);
+        };
+
+        log_info!("Loading the most recent run {path:?}");
+        if path.extension().map_or(false, |ext| ext == "bin") {
+            self.import_inputs(&path)?;
+        } else {
+            self.load_run(&path)?;
+            self.save_file = path.to_string_lossy().into_owned();
+        }
+        Ok(true)
+    }
+
+    /// Replaces the inputs (e.g. with ones edited by an external tool)
+    /// and starts replaying them from the initial state.
+    /// This is the in-memory counterpart of [Self::load_run], see also [Self::inputs].
+    pub fn set_inputs(&mut self, inputs: Vec<FrameInput<geng::Event>>) {
+        let Some(initial_state) = self.initial_state().cloned() else {
+            return;
+        };
+        self.snapshot_before_load();
+        self.game.load(initial_state);
+        self.load_initial_rng();
+        self.pressed_keys.clear();
+        self.pressed_buttons.clear();
+        self.replay_inputs_from_current(inputs);
+    }
+
+    /// Replays the inputs starting from the current game state instead of the initial one.
+    ///
+    /// Since the game is likely in a different state than when the inputs were recorded,
+    /// making sure the replay is meaningful (and deterministic) is up to the caller.
+    pub fn replay_inputs_from_current(&mut self, inputs: Vec<FrameInput<geng::Event>>) {
+        self.frame = 0;
+        self.queued_inputs.clear();
+        self.queued_timestamps.clear();
+        self.inputs.clear();
+        self.history.clear();
+        self.continue_after_replay = false;
+        self.replay = Some(Replay::new(
+            History::new(self.config.snapshot_interval, self.game.save()),
+            inputs,
+        ));
+    }
+
+    fn load_savestates(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = self.config.savestates_dir.clone() {
+            return self.load_savestates_dir(&dir);
+        }
+        let Ok(bytes) = std::fs::read("savedstates.json") else {
+            log_warn!("Failed to open savedstates.json");
+            self.saved_states = default();
+            return Ok(());
+        };
+        self.load_states_from_bytes(&bytes)
+    }
+
+    /// Loads the save states stored one per file, see [TasConfig::savestates_dir].
+    /// Files that fail to load are skipped.
+    fn load_savestates_dir(&mut self, dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            log_warn!("Failed to open the save state directory {dir}");
+            self.saved_states = default();
+            return Ok(());
+        };
+        let mut files: Vec<(usize, std::path::PathBuf)> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let index = path
+                    .file_name()?
+                    .to_str()?
+                    .strip_prefix("state_")?
+                    .strip_suffix(".json")?
+                    .parse()
+                    .ok()?;
+                Some((index, path))
+            })
+            .collect();
+        files.sort_by_key(|&(index, _)| index);
+
+        self.saved_states.clear();
+        for (_, path) in files {
+            let result = std::fs::read(&path)
+                .map_err(Box::<dyn std::error::Error>::from)
+                .and_then(|bytes| {
+                    let stored: SaveState<StoredState> = serde_json::from_slice(&bytes)?;
+                    let mut decoder = DeltaDecoder::default();
+                    stored.try_map(|saved| decoder.restore::<T>(saved))
+                });
+            match result {
+                Ok(mut state) => {
+                    migrate_save_state(&self.game, &mut state);
+                    state.size = stored_size::<T>(&state);
+                    self.saved_states.push(state);
+                }
+                Err(err) => log_error!("Failed to load the save state {path:?}: {err}"),
+            }
+        }
+        self.selected_state = 0;
+        self.loaded_state = None;
+        if self.config.dedup_savestates {
+            self.dedup_savestates()?;
+        }
+        Ok(())
+    }
+
+    /// Replaces all save states with the ones serialized with [Self::save_states_to_bytes].
+    pub fn load_states_from_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let stored: Vec<SaveState<StoredState>> = serde_json::from_slice(bytes)?;
+        let mut decoder = DeltaDecoder::default();
+        self.saved_states = stored
+            .iter()
+            .map(|state| state.try_map(|saved| decoder.restore::<T>(saved)))
+            .collect::<Result<_, _>>()?;
+        for state in &mut self.saved_states {
+            migrate_save_state(&self.game, state);
+            state.size = stored_size::<T>(state);
+        }
+        self.selected_state = 0;
+        self.loaded_state = None;
+        if self.config.dedup_savestates {
+            self.dedup_savestates()?;
+        }
+        Ok(())
+    }
+
+    /// Merges consecutive save states with identical game states,
+    /// keeping the earlier one and combining their names.
+    fn dedup_savestates(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let states = std::mem::take(&mut self.saved_states);
+        let mut last_bytes = None;
+        for state in states {
+            let bytes = serde_json::to_vec(state.game_state())?;
+            if let Some(last) = self
+                .saved_states
+                .last_mut()
+                .filter(|_| last_bytes.as_ref() == Some(&bytes))
+            {
+                last.name = match (last.name.take(), state.name) {
+                    (Some(a), Some(b)) if a != b => Some(format!("{a} / {b}")),
+                    (a, b) => a.or(b),
+                };
+                continue;
+            }
+            self.saved_states.push(state);
+            last_bytes = Some(bytes);
+        }
+        Ok(())
+    }
+
+    /// Whether interpolated frames should be drawn.
+    fn interpolating(&self) -> bool {
+        self.config.interpolation || self.config.replay_interpolation && self.replay.is_some()
+    }
+
+    /// Checks whether the game is in the same state as the given one.
+    fn matches_state(&self, state: &T::Saved) -> bool {
+        let current = serde_json::to_value(self.game.save());
+        let expected = serde_json::to_value(state);
+        match (current, expected) {
+            (Ok(current), Ok(expected)) => current == expected,
+            _ => true,
+        }
+    }
+
+    /// Moves the cursor to where it was in the simulation, if known.
+    fn restore_cursor(&mut self, cursor: Option<vec2<f64>>) {
+        self.cursor_position = cursor;
+        if let Some(position) = cursor {
+            self.set_window_cursor(position);
+        }
+    }
+
+    /// Whether the pressed state is synced by setting it on the window,
+    /// see [TasConfig::sync_pressed_state].
+    fn syncs_window(&self) -> bool {
+        self.config.sync_pressed_state && self.window_sync_supported && self.geng.is_some()
+    }
+
+    /// Makes the window report the simulated pressed keys and buttons.
+    ///
+    /// If the window does not support setting them (or syncing is disabled), the game is sent
+    /// synthetic events for the keys and buttons that changed since the last sync,
+    /// e.g. after loading a state.
+    fn sync_pressed_state(&mut self) {
+        if self.syncs_window() {
+            let window = self.geng().window();
+            window.set_pressed_keys(self.pressed_keys.clone());
+            window.set_pressed_buttons(self.pressed_buttons.clone());
+
+            let synced = self
+                .pressed_keys
+                .iter()
+                .all(|&key| window.is_key_pressed(key))
+                && self
+                    .pressed_buttons
+                    .iter()
+                    .all(|&button| window.is_button_pressed(button));
+            if synced {
+                self.synced_keys.clone_from(&self.pressed_keys);
+                self.synced_buttons.clone_from(&self.pressed_buttons);
+                return;
+            }
+            log_warn!(
+                "The window does not support setting pressed keys and buttons, \
+                falling back to synthetic events"
+            );
+            self.window_sync_supported = false;
+        }
+
+        let position = self.window_cursor();
+        let mut events = Vec::new();
+        events.extend(
+            self.synced_keys
+                .difference(&self.pressed_keys)
+                .map(|&key| geng::Event::KeyUp { key }),
+        );
+        events.extend(
+            self.synced_buttons
+                .difference(&self.pressed_buttons)
+                .map(|&button| geng::Event::MouseUp { position, button }),
+        );
+        events.extend(
+            self.pressed_keys
+                .difference(&self.synced_keys)
+                .map(|&key| geng::Event::KeyDown { key }),
+        );
+        events.extend(
+            self.pressed_buttons
+                .difference(&self.synced_buttons)
+                .map(|&button| geng::Event::MouseDown { position, button }),
+        );
+        for event in events {
+            self.game.handle_event(event);
+        }
+        self.synced_keys.clone_from(&self.pressed_keys);
+        self.synced_buttons.clone_from(&self.pressed_buttons);
+    }
 
     /// Plays the next frame (either in replay or record mode).
     fn next_frame(&mut self) {
+        if self.initial_state_pending {
+            self.initial_state_pending = false;
+            if self.replay.is_none() && self.frame == 0 && self.inputs.is_empty() {
+                // The game should be fully initialized by now
+                self.history = History::new(self.config.snapshot_interval, self.game.save());
+                self.initial_rng = self.game.save_rng();
+                self.log_dirty = true;
+            }
+        }
+
+        // Restore the replayed state
+        if let Some(state) = self
+            .replay
+            .as_ref()
+            .and_then(|replay| replay.history.get_exact(replay.frame))
+            .cloned()
+        {
+            if self.config.detect_desync
+                && self.desync_frame != Some(self.frame)
+                && !self.matches_state(&state)
+            {
+                let frame = self.frame;
+                log_warn!("Replay desynced at frame {frame}");
+                self.notify(format!("Replay desynced at frame {frame}"));
+                self.desync_frame = Some(frame);
+                match self.config.desync_policy {
+                    DesyncPolicy::Continue => {}
+                    DesyncPolicy::ReloadCheckpoint => self.game.load(state),
+                    DesyncPolicy::Pause => {
+                        // Resuming continues from the recorded state
+                        self.paused = true;
+                        return;
+                    }
+                    DesyncPolicy::Abort => {
+                        if self.continue_after_replay {
+                            self.take_over_replay();
+                        }
+                        self.replay = None;
+                        self.paused = true;
+                        return;
+                    }
+                    DesyncPolicy::RetryFromStart { max_retries } => {
+                        let replay = self.replay.as_mut().unwrap();
+                        if replay.retries < max_retries {
+                            replay.retries += 1;
+                            log_warn!(
+                                "Retrying the replay from the start ({}/{max_retries})",
+                                replay.retries
+                            );
+                            self.desync_frame = None;
+                            self.seek(0);
+                        } else {
+                            log_warn!("The replay still desyncs after {max_retries} retries");
+                            // Resuming continues from the recorded state
+                            self.paused = true;
+                        }
+                        return;
+                    }
+                }
+            } else {
+                self.game.load(state);
+            }
+        }
+
+        if self.interpolating() {
+            self.previous_state = Some(self.game.save());
+        }
+
+        // Sync pressed states
+        self.sync_pressed_state();
+
+        if self.replay.is_none() {
+            // Apply the macro before the live inputs
+            if let Some(inputs) = self.pending_macro.pop_front() {
+                if self.config.record_timestamps {
+                    self.queued_timestamps.splice(0..0, vec![0.0; inputs.len()]);
+                }
+                self.queued_inputs.splice(0..0, inputs);
+            }
+            if self.config.remove_taps {
+                let len = self.queued_inputs.len();
+                remove_taps(&mut self.queued_inputs);
+                if self.queued_inputs.len() != len {
+                    // The timestamps no longer match the events
+                    self.queued_timestamps.clear();
+                }
+            }
+        }
+
+        // Only record the inputs without touching the game
+        let record_only = self.config.record_only && self.replay.is_none();
+
         // Get frame inputs
-        let inputs = if let Some(replay) = &self.replay {
+        if let Some(replay) = &mut self.replay {
+            replay.fetch_input();
+        }
+        let (inputs, timestamps) = if let Some(replay) = &self.replay {
             match replay.inputs.get(replay.input) {
-                Some(inputs) => &inputs.inputs,
+                Some(inputs) => (&inputs.inputs, &inputs.timestamps),
                 None => {
                     // TODO: indicate that the replay has ended or smth
-                    self.paused = true;
+                    if self.continue_after_replay {
+                        self.take_over_replay();
+                    } else if !self.play_next_run() {
+                        self.paused = true;
+                    }
                     return;
                 }
             }
         } else {
-            &self.queued_inputs
+            (&self.queued_inputs, &self.queued_timestamps)
         };
 
-        // Sync pressed states
-        self.geng
-            .window()
-            .set_pressed_keys(self.pressed_keys.clone());
-        self.geng
-            .window()
-            .set_pressed_buttons(self.pressed_buttons.clone());
-
-        // Simulate inputs
-        for input in inputs {
+        // The delta time to update the game with on this frame
+        let update_delta_time = match &self.replay {
+            Some(replay) => replay
+                .inputs
+                .get(replay.input)
+                .and_then(|input| input.delta_time),
+            None => self.frame_delta_time,
+        }
+        .unwrap_or(self.fixed_delta_time);
+
+        // Reproduce the recorded cursor position
+        let cursor = match &self.replay {
+            Some(replay) => replay
+                .inputs
+                .get(replay.input)
+                .and_then(|input| input.cursor),
+            None => self.cursor_override,
+        };
+        if let Some(position) = cursor {
+            self.set_window_cursor(position);
+            self.cursor_position = Some(position);
+        }
+
+        // Simulate inputs in the same order they are recorded in,
+        // or in the order of their timestamps if there are any
+        let mut order: Vec<usize> = (0..inputs.len()).collect();
+        if timestamps.len() == inputs.len() {
+            order.sort_by(|&a, &b| timestamps[a].total_cmp(&timestamps[b]));
+        }
+        self.simulation.enter();
+        for input in order.into_iter().map(|i| &inputs[i]) {
             // Update pressed states
-            match input {
-                geng::Event::KeyDown { key } => {
-                    self.pressed_keys.insert(*key);
+            apply_pressed(input, &mut self.pressed_keys, &mut self.pressed_buttons);
+            if let Some(position) = event_position(input) {
+                self.cursor_position = Some(position);
+            }
+            // Sync pressed states
+            if self.syncs_window() {
+                self.geng()
+                    .window()
+                    .set_pressed_keys(self.pressed_keys.clone());
+                self.geng()
+                    .window()
+                    .set_pressed_buttons(self.pressed_buttons.clone());
+            }
+
+            if !record_only {
+                self.game.handle_event(input.clone());
+            }
+        }
+        // The game has seen all the changes through the events
+        self.synced_keys.clone_from(&self.pressed_keys);
+        self.synced_buttons.clone_from(&self.pressed_buttons);
+
+        // Update inputs
+        if let Some(replay) = &mut self.replay {
+            // Get next input
+            replay.next_input = replay.next_input.saturating_sub(1);
+            if replay.next_input == 0 {
+                replay.input += 1;
+                replay.fetch_input();
+                if let Some(next) = replay.inputs.get(replay.input) {
+                    replay.next_input = next.frames;
+                }
+            }
+
+            replay.frame += 1;
+        } else {
+            // Record the inputs
+            let input = FrameInput {
+                frames: 1,
+                inputs: std::mem::take(&mut self.queued_inputs),
+                timestamps: std::mem::take(&mut self.queued_timestamps),
+                cursor: self
+                    .cursor_override
+                    .take()
+                    .or_else(|| self.config.record_cursor.then(|| self.window_cursor())),
+                delta_time: self.frame_delta_time.take(),
+            };
+            self.check_rerecord(&input.inputs);
+            if self.config.record_pacing && !self.resimulating {
+                // Frames recorded without pacing take the uniform time
+                self.frame_intervals
+                    .resize(self.frame, self.fixed_delta_time);
+                self.frame_intervals
+                    .push(std::mem::take(&mut self.pacing_time));
+            }
+            if !input.inputs.is_empty() {
+                // Diverged from the loaded state
+                self.loaded_state = None;
+            }
+            if let Some(last) = self.inputs.last_mut().filter(|last| last.same_as(&input)) {
+                // Extend last input
+                last.frames += 1;
+            } else {
+                // Create new input
+                self.inputs.push(input);
+            }
+            self.append_log();
+            self.update_reference_match();
+        }
+        // The events arriving from now on belong to the next frame
+        self.frame_time = 0.0;
+
+        // Update
+        if !record_only {
+            if let Some(source) = &self.external_states {
+                // The state comes from outside, so the simulation is skipped
+                match source.try_recv() {
+                    Ok(state) => self.game.load(state),
+                    Err(std::sync::mpsc::TryRecvError::Empty) => {}
+                    Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                        log_warn!("External state source disconnected, resuming simulation");
+                        self.external_states = None;
+                    }
+                }
+            } else if self.config.catch_panics {
+                let game = &mut self.game;
+                let delta_time = self.fixed_delta_time;
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    game.update(update_delta_time);
+                    game.fixed_update(delta_time);
+                }));
+                if result.is_err() {
+                    // The game state may be broken now, so pause to let the user inspect it
+                    self.notify_error(format!(
+                        "The game panicked while simulating frame {}",
+                        self.frame
+                    ));
+                    self.paused = true;
                 }
-                geng::Event::KeyUp { key } => {
-                    self.pressed_keys.remove(key);
+            } else {
+                self.game.update(update_delta_time);
+                self.game.fixed_update(self.fixed_delta_time);
+            }
+        }
+        self.simulation.exit();
+
+        let reloaded = self.game.take_state_reload();
+        if reloaded {
+            // The previous frame is unrelated to the reloaded state
+            self.previous_state = None;
+            log_info!("The game reloaded its state on frame {}", self.frame);
+        }
+
+        self.frame += 1;
+        self.sync_comparison();
+
+        self.game.on_frame(self.frame - 1);
+        if let Some(callback) = &mut self.frame_callback {
+            callback(self.frame - 1, &self.game);
+        }
+
+        let breakpoint = self
+            .breakpoints
+            .iter()
+            .any(|predicate| predicate(&self.game))
+            || self.replay.is_some() && self.input_breakpoint == Some(self.frame);
+
+        if self.replay.is_none() {
+            // Snapshot the state at the start of the next frame
+            let game = &self.game;
+            self.history.record(self.frame, || game.save());
+            if reloaded {
+                // Seeking has to restore the reloaded state instead of simulating into it
+                self.history.force(self.frame, self.game.save());
+            }
+        }
+
+        if breakpoint {
+            self.input_breakpoint = None;
+            self.paused = true;
+            log_info!("Breakpoint at frame {}", self.frame);
+            self.notify(format!("Breakpoint at frame {}", self.frame));
+            if self.config.snapshot_on_breakpoint {
+                self.push_state(Some(format!("breakpoint @frame {}", self.frame)), false);
+            }
+        }
+
+        if self.replay.is_none() {
+            // Only save once the condition becomes true, and not again when seeking over it
+            let requested = self.game.should_autosave();
+            if requested && !self.last_autosave_condition && !self.resimulating {
+                self.push_auto_snapshot(format!("autosave @frame {}", self.frame));
+            }
+            self.last_autosave_condition = requested;
+        }
+
+        if self.replay.is_none() {
+            // Seeking over the finish only keeps track of it
+            let finished = self.game.is_finished();
+            if finished && !self.finished && !self.resimulating {
+                self.finish_run();
+            }
+            self.finished = finished;
+        }
+    }
+
+    /// Pauses the recording once the game reports it as finished,
+    /// saving the run if [TasConfig::finished_runs_dir] is set.
+    fn finish_run(&mut self) {
+        self.paused = true;
+        let goal = match self.goal_frame {
+            Some(goal) if self.frame <= goal => format!(" ({} under the goal)", goal - self.frame),
+            Some(goal) => format!(" ({} over the goal)", self.frame - goal),
+            None => String::new(),
+        };
+        log_info!("Run finished at frame {}{goal}", self.frame);
+        self.notify(format!("Run finished at frame {}{goal}", self.frame));
+
+        let Some(dir) = self.config.finished_runs_dir.clone() else {
+            return;
+        };
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let path =
+            std::path::Path::new(&dir).join(format!("finished_{}_{timestamp}.json", self.frame));
+        if let Err(err) = self.save_run(&path) {
+            self.notify_error(format!("Failed to save the finished run: {err}"));
+        }
+    }
+
+    /// Queues the event to be recorded and applied on the next frame,
+    /// the same way as the events coming from the window.
+    /// A pressed key stays held until its release is queued as well.
+    pub fn queue_event(&mut self, event: geng::Event) {
+        if self.replay.is_some() || !self.simulation.accepts(&event) {
+            return;
+        }
+
+        if !self.config.records(&event) {
+            // Not part of the run, so the game gets it right away
+            self.game.handle_event(event);
+            return;
+        }
+
+        if let geng::Event::KeyDown { key } = event {
+            if !self.config.record_key_repeats
+                && key_held(&self.pressed_keys, &self.queued_inputs, key)
+            {
+                // A repeat of a held key
+                return;
+            }
+        }
+
+        if let Some(event) = self.game.filter_event(&event) {
+            self.queued_inputs.push(event);
+            if self.config.record_timestamps {
+                self.queued_timestamps.push(self.frame_time);
+            }
+        }
+    }
+
+    /// Queues the releases of every held key and button, so that they do not get stuck
+    /// when the window loses focus and the releases never arrive.
+    /// Once the focus is back, the keys still held have to be pressed again.
+    fn release_all_inputs(&mut self) {
+        if let MenuActivation::Hold = self.config.menu_activation {
+            self.menu_open = false;
+        }
+        self.ui_peeking = false;
+        if self.replay.is_some() {
+            return;
+        }
+
+        let mut keys = self.pressed_keys.clone();
+        let mut buttons = self.pressed_buttons.clone();
+        for event in &self.queued_inputs {
+            apply_pressed(event, &mut keys, &mut buttons);
+        }
+        let position = self.window_cursor();
+        for key in keys {
+            self.queue_event(geng::Event::KeyUp { key });
+        }
+        for button in buttons {
+            self.queue_event(geng::Event::MouseUp { position, button });
+        }
+    }
+
+    /// Schedules the event to be recorded and applied once at the start of the next frame.
+    /// Unlike with [Self::queue_event], a key or button press is released on the frame after,
+    /// so a discrete action can be triggered without managing the release.
+    /// The injected events are applied before the queued ones, the same way as macros.
+    pub fn inject_once(&mut self, event: geng::Event) {
+        if self.replay.is_some() {
+            return;
+        }
+        let release = match &event {
+            geng::Event::KeyDown { key } => Some(geng::Event::KeyUp { key: *key }),
+            geng::Event::MouseDown { position, button } => Some(geng::Event::MouseUp {
+                position: *position,
+                button: *button,
+            }),
+            _ => None,
+        };
+        let frames = if release.is_some() { 2 } else { 1 };
+        while self.pending_macro.len() < frames {
+            self.pending_macro.push_back(Vec::new());
+        }
+        self.pending_macro[0].push(event);
+        if let Some(release) = release {
+            self.pending_macro[1].push(release);
+        }
+    }
+
+    /// Pauses the simulation and simulates the given number of frames.
+    pub fn step(&mut self, frames: usize) {
+        self.paused = true;
+        for _ in 0..frames {
+            let frame = self.frame;
+            self.next_frame();
+            if self.frame == frame {
+                // Replay has ended
+                break;
+            }
+        }
+    }
+
+    /// Simulates as many frames as fit into the given game time (in seconds),
+    /// e.g. for scripts thinking in seconds rather than frames.
+    /// The remainder (less than a frame) carries over to the next call, so that
+    /// repeated calls do not drift, but is dropped once the simulation is paused.
+    pub fn advance_time(&mut self, seconds: f64) {
+        self.clock.advance(seconds);
+        while self.clock.take_frame(self.fixed_delta_time) {
+            let frame = self.frame;
+            self.next_frame();
+            if self.frame == frame {
+                // Replay has ended
+                self.clock.reset();
+                break;
+            }
+        }
+    }
+
+    /// Simulates frames until the predicate holds for the game or `max_frames` frames pass,
+    /// then pauses the simulation.
+    /// Returns whether the condition has been reached.
+    pub fn step_until(&mut self, predicate: impl Fn(&T) -> bool, max_frames: usize) -> bool {
+        self.paused = true;
+        for _ in 0..max_frames {
+            if predicate(&self.game) {
+                return true;
+            }
+            let frame = self.frame;
+            self.next_frame();
+            if self.frame == frame {
+                // Replay has ended
+                break;
+            }
+        }
+        predicate(&self.game)
+    }
+
+    /// Replaces the events applied on the given frame and simulates the game again from there.
+    /// While recording, only the frames before the current one can be edited.
+    pub fn set_frame_inputs(&mut self, frame: usize, inputs: Vec<geng::Event>) {
+        if let Some(replay) = &mut self.replay {
+            set_frame(&mut replay.inputs, frame, inputs);
+            replay.total_frames = total_frames(&replay.inputs);
+            replay.history.truncate(frame);
+        } else {
+            if frame >= self.frame {
+                log_warn!("Cannot edit frame {frame}, it has not been recorded yet");
+                return;
+            }
+            set_frame(&mut self.inputs, frame, inputs);
+            self.history.truncate(frame);
+            self.log_dirty = true;
+        }
+        self.seek(self.frame);
+    }
+
+    /// Removes the frames from `start` to `end` from the run, joining the frames around them,
+    /// and simulates it again up to the same place (or to `start` if it was removed).
+    /// While recording, only the recorded frames can be removed.
+    pub fn trim_frames(
+        &mut self,
+        start: usize,
+        end: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if start >= end || end > self.total_frames() {
+            return Err(format!("Invalid trim range {start}..{end}").into());
+        }
+        let frame = if self.frame >= end {
+            self.frame - (end - start)
+        } else {
+            self.frame.min(start)
+        };
+        let (inputs, history, render_hashes, frame_intervals) = match &mut self.replay {
+            Some(replay) => (
+                &mut replay.inputs,
+                &mut replay.history,
+                &mut replay.render_hashes,
+                &mut replay.frame_intervals,
+            ),
+            None => (
+                &mut self.inputs,
+                &mut self.history,
+                &mut self.render_hashes,
+                &mut self.frame_intervals,
+            ),
+        };
+        remove_frames(inputs, start..end);
+        history.truncate(start);
+        render_hashes.retain(|&hashed, _| hashed < start);
+        let len = frame_intervals.len();
+        frame_intervals.drain(start.min(len)..end.min(len));
+        match &mut self.replay {
+            Some(replay) => replay.total_frames = total_frames(&replay.inputs),
+            None => self.log_dirty = true,
+        }
+        self.seek(frame);
+        Ok(())
+    }
+
+    /// The inputs of the current run: the replayed ones during a replay,
+    /// and the recorded ones otherwise.
+    pub fn inputs(&self) -> &[FrameInput<geng::Event>] {
+        match &self.replay {
+            Some(replay) => &replay.inputs,
+            None => &self.inputs,
+        }
+    }
+
+    /// Splits the current run into (at most) `buckets` equal parts
+    /// and computes the fraction of frames with any input in each of them.
+    /// Useful for spotting idle stretches.
+    pub fn input_density(&self, buckets: usize) -> Vec<f64> {
+        input_density(self.inputs(), buckets)
+    }
+
+    /// Returns the events applied on the given frame.
+    fn frame_events(&self, frame: usize) -> Vec<geng::Event> {
+        frame_inputs(self.inputs(), frame..frame + 1)
+            .pop()
+            .unwrap_or_default()
+    }
+
+    /// Moves the simulation to the given frame.
+    ///
+    /// During a replay, the game is restored from the nearest snapshot
+    /// and simulated up to the target frame.
+    /// While recording, only earlier frames can be reached:
+    /// the recorded inputs up to the target are simulated again,
+    /// and everything after it is discarded.
+    pub fn seek(&mut self, frame: usize) {
+        if let Some(replay) = &mut self.replay {
+            let target = frame.min(replay.total_frames);
+            let Some((start, state)) = replay.history.nearest(target) else {
+                return;
+            };
+            self.game.load(state.clone());
+            replay.frame = start;
+            if start == 0 && !self.initial_rng.is_empty() {
+                self.game.load_rng(&self.initial_rng);
+            }
+            (replay.input, replay.next_input) = input_position(&replay.inputs, start);
+            (self.pressed_keys, self.pressed_buttons) = pressed_at(&replay.inputs, start);
+            let cursor = cursor_at(&replay.inputs, start);
+            self.frame = start;
+            self.restore_cursor(cursor);
+
+            while self.frame < target {
+                let frame = self.frame;
+                self.next_frame();
+                if self.frame == frame {
+                    // Replay has ended
+                    break;
                 }
-                geng::Event::MouseDown { button, .. } => {
-                    self.pressed_buttons.insert(*button);
+            }
+        } else {
+            let target = frame.min(self.frame);
+            let Some((start, state)) = self.history.nearest(target) else {
+                return;
+            };
+            self.game.load(state.clone());
+            if start == 0 {
+                self.load_initial_rng();
+            }
+            let resimulate = expand_inputs(&self.inputs, start..target);
+            self.rerecord_reference = Some(self.inputs.clone());
+            truncate_inputs(&mut self.inputs, start);
+            self.history.truncate(start);
+            self.log_dirty = true;
+            (self.pressed_keys, self.pressed_buttons) = pressed_at(&self.inputs, start);
+            self.restore_cursor(cursor_at(&self.inputs, start));
+            self.frame = start;
+
+            // The frames were already recorded, so their intervals are kept
+            self.resimulating = true;
+            self.record_inputs(resimulate);
+            self.resimulating = false;
+        }
+    }
+
+    /// Plays the replay backwards (or forwards again), by seeking one frame back at a time.
+    /// Every step simulates the frames from the nearest earlier snapshot, so it is smooth
+    /// with a small [TasConfig::snapshot_interval]. Only works during a replay,
+    /// and stops at its first frame.
+    pub fn set_reverse(&mut self, reverse: bool) {
+        self.reversing = reverse && self.replay.is_some();
+    }
+
+    /// Simulates the next frame, or goes a frame back when playing the replay backwards.
+    fn advance_frame(&mut self) {
+        if !self.reversing {
+            self.next_frame();
+            self.loop_back();
+            return;
+        }
+        if self.replay.is_none() || self.frame == 0 {
+            self.reversing = false;
+            self.paused = true;
+            return;
+        }
+        self.seek(self.frame - 1);
+    }
+
+    /// Seeks to the nearest frame a save state was saved on after the current one,
+    /// without loading the state itself. Returns that frame, if there is one.
+    pub fn seek_next_state(&mut self) -> Option<usize> {
+        let frame = self
+            .saved_states
+            .iter()
+            .map(|state| state.frame)
+            .filter(|&frame| frame > self.frame)
+            .min()?;
+        self.seek(frame);
+        Some(frame)
+    }
+
+    /// Seeks to the nearest frame a save state was saved on before the current one,
+    /// without loading the state itself. Returns that frame, if there is one.
+    pub fn seek_prev_state(&mut self) -> Option<usize> {
+        let frame = self
+            .saved_states
+            .iter()
+            .map(|state| state.frame)
+            .filter(|&frame| frame < self.frame)
+            .max()?;
+        self.seek(frame);
+        Some(frame)
+    }
+
+    /// Seeks to the frame at the given time (in seconds) into the run.
+    pub fn seek_to_time(&mut self, seconds: f64) {
+        let frame = (seconds.max(0.0) / self.fixed_delta_time).round() as usize;
+        self.seek(frame);
+    }
+
+    /// The time until the next frame should be simulated: the recorded one when replaying
+    /// with [TasConfig::replay_pacing], or the fixed delta time.
+    fn frame_interval(&self) -> f64 {
+        self.replay
+            .as_ref()
+            .filter(|replay| self.config.replay_pacing && replay.frame < replay.total_frames)
+            .and_then(|replay| replay.frame_intervals.get(replay.frame).copied())
+            .unwrap_or(self.fixed_delta_time)
+    }
+
+    /// Sets the time scale, limited by [TasConfig::max_time_scale].
+    pub(crate) fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.clamp(0.0, self.config.max_time_scale);
+    }
+
+    /// Lists the inputs held around the current frame, one row per frame,
+    /// if enabled with [TasConfig::input_strip_frames] and [Keybinds::input_overlay].
+    fn input_overlay(&self) -> Option<impl geng::ui::Widget> {
+        use geng::ui::{column, *};
+
+        let radius = self.config.input_strip_frames;
+        if radius == 0 || !self.show_input_overlay {
+            return None;
+        }
+        let text_size = self.framebuffer_size.y as f32 * 0.05;
+        let strip_start = self.frame.saturating_sub(radius);
+        let strip_end = (self.frame + radius + 1).min(self.total_frames());
+        let rows = held_inputs(self.inputs(), strip_start..strip_end)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (keys, buttons))| {
+                let frame = strip_start + i;
+                let mut held: Vec<String> = keys.iter().map(|key| format!("{key:?}")).collect();
+                held.extend(buttons.iter().map(|button| format!("Mouse{button:?}")));
+                held.sort();
+                let color = if frame == self.frame {
+                    Rgba::YELLOW
+                } else {
+                    Rgba::WHITE
+                };
+                geng::ui::Text::new(
+                    format!("{frame}: {}", held.join(" ")),
+                    self.geng().default_font().clone(),
+                    text_size * 0.4,
+                    color,
+                )
+                .boxed()
+            })
+            .collect();
+        Some(column(rows))
+    }
+
+    /// Draws the game at the frame hovered on the timeline in the corner of the screen,
+    /// from the nearest snapshot. The preview is only rendered again once another frame is hovered.
+    fn draw_preview(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        let frame = self.preview_frame.filter(|_| self.config.scrub_preview);
+        let Some(frame) = frame else {
+            self.preview = None;
+            return;
+        };
+
+        if self.preview.as_ref().map(|(preview, _)| *preview) != Some(frame) {
+            let history = match &self.replay {
+                Some(replay) => &replay.history,
+                None => &self.history,
+            };
+            let Some((_, state)) = history.nearest(frame) else {
+                return;
+            };
+            let state = state.clone();
+
+            let geng = self.geng().clone();
+            let size = framebuffer.size().map(|x| (x / 4).max(1));
+            let mut texture = ugli::Texture::new_uninitialized(geng.ugli(), size);
+            let current = self.game.save();
+            self.game.load(state);
+            {
+                let mut framebuffer = ugli::Framebuffer::new_color(
+                    geng.ugli(),
+                    ugli::ColorAttachment::Texture(&mut texture),
+                );
+                ugli::clear(&mut framebuffer, Some(Rgba::BLACK), None, None);
+                self.game.draw(&mut framebuffer);
+            }
+            self.game.load(current);
+            self.preview = Some((frame, texture));
+        }
+
+        if let Some((_, texture)) = &self.preview {
+            let screen = framebuffer.size().map(|x| x as f32);
+            let size = texture.size().map(|x| x as f32);
+            self.geng().draw2d().draw2d(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw2d::TexturedQuad::new(
+                    Aabb2::from_corners(vec2(0.0, screen.y - size.y), vec2(size.x, screen.y)),
+                    texture,
+                ),
+            );
+        }
+    }
+
+    /// Sets the window title from the template in [TasConfig::window_title].
+    fn update_window_title(&self) {
+        let Some(template) = &self.config.window_title else {
+            return;
+        };
+        let total = self.total_frames();
+        let status = if self.replay.is_some() {
+            "Replay"
+        } else {
+            "Recording"
+        };
+        let title = template
+            .replace("{status}", status)
+            .replace("{frame}", &self.frame.to_string())
+            .replace("{total}", &total.to_string())
+            .replace(
+                "{percent}",
+                &format!("{:.0}", self.frame as f64 / total.max(1) as f64 * 100.0),
+            );
+        if let Some(geng) = &self.geng {
+            geng.window().set_title(&title);
+        }
+    }
+
+    /// Pauses the simulation on the user's request.
+    pub(crate) fn pause(&mut self) {
+        self.paused = true;
+        if self.config.clear_queued_on_pause {
+            self.queued_inputs.clear();
+            self.queued_timestamps.clear();
+        }
+    }
+
+    /// The number of frames in the current replay or recording.
+    pub fn total_frames(&self) -> usize {
+        match &self.replay {
+            Some(replay) => replay.total_frames,
+            // The recording always ends at the current frame
+            None => self.frame,
+        }
+    }
+
+    /// Replays the frames from `start` to `end` over and over,
+    /// seeking back to `start` every time the replay reaches `end`.
+    pub fn loop_range(
+        &mut self,
+        start: usize,
+        end: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(replay) = &self.replay else {
+            return Err("Only a replay can be looped".into());
+        };
+        if start >= end || end > replay.total_frames {
+            return Err(format!("Invalid loop range {start}..{end}").into());
+        }
+        self.looped_range = Some(start..end);
+        self.seek(start);
+        Ok(())
+    }
+
+    /// Stops looping the replay, see [Self::loop_range].
+    pub fn stop_loop(&mut self) {
+        self.looped_range = None;
+    }
+
+    /// Seeks back to the start of the looped range once its end is reached.
+    fn loop_back(&mut self) {
+        if self.replay.is_none() {
+            self.looped_range = None;
+            return;
+        }
+        if let Some(range) = self.looped_range.clone() {
+            if self.frame >= range.end {
+                self.seek(range.start);
+            }
+        }
+    }
+
+    /// Counts a rerecord if the events recorded on the current frame differ
+    /// from the ones recorded before the last load.
+    ///
+    /// Loading a state (or seeking back) only counts as a rerecord
+    /// once new inputs are recorded before reaching the end of the previous recording,
+    /// so loading just to watch the same inputs again does not count.
+    fn check_rerecord(&mut self, events: &[geng::Event]) {
+        let Some(reference) = &self.rerecord_reference else {
+            return;
+        };
+        if self.frame >= total_frames(reference) {
+            // Reached the end of the previous recording without diverging
+            self.rerecord_reference = None;
+        } else if frame_inputs(reference, self.frame..self.frame + 1)[0] != events {
+            self.rerecords += 1;
+            self.rerecord_reference = None;
+        }
+    }
+
+    /// Simulates and records the single-frame inputs one by one,
+    /// keeping the inputs queued in the meantime for the next frame.
+    fn record_inputs(&mut self, inputs: Vec<FrameInput<geng::Event>>) {
+        let queued = std::mem::take(&mut self.queued_inputs);
+        let queued_timestamps = std::mem::take(&mut self.queued_timestamps);
+        for input in inputs {
+            self.queued_inputs = input.inputs;
+            self.queued_timestamps = input.timestamps;
+            self.cursor_override = input.cursor;
+            self.frame_delta_time = input.delta_time;
+            self.next_frame();
+        }
+        self.queued_inputs = queued;
+        self.queued_timestamps = queued_timestamps;
+    }
+
+    /// Appends the inputs of another run to the recording, simulating them from the current state.
+    /// The initial state of the other run is ignored, so its inputs may have been recorded
+    /// from a completely different state. Whether they still make sense from here
+    /// (and the result is deterministic) is up to the caller.
+    pub fn splice_run(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.replay.is_some() {
+            return Err("Runs can only be spliced onto a recording, not a replay".into());
+        }
+        let bytes = std::fs::read(path)?;
+        let mut saved = read_run::<T>(&bytes)?;
+        migrate_run(&self.game, &mut saved);
+        log_warn!(
+            "Splicing a run onto frame {}, the inputs may not be meaningful from this state",
+            self.frame
+        );
+        let frames = total_frames(&saved.inputs);
+        self.record_inputs(expand_inputs(&saved.inputs, 0..frames));
+        Ok(())
+    }
+
+    /// Checks that re-simulating the recorded inputs from the initial state
+    /// reproduces the current game state, see [Self::first_divergent_frame].
+    pub fn verify_current_state(&mut self) -> bool
+    where
+        T::Saved: PartialEq,
+    {
+        self.first_divergent_frame().is_none()
+    }
+
+    /// Re-simulates the recorded inputs from the initial state and returns the first frame
+    /// at which the game state differs from the recorded snapshots or the current state.
+    /// The current game state is restored afterwards.
+    pub fn first_divergent_frame(&mut self) -> Option<usize>
+    where
+        T::Saved: PartialEq,
+    {
+        let initial = self.initial_state()?.clone();
+        let current = self.game.save();
+        self.game.load(initial);
+
+        let mut pressed_keys = HashSet::new();
+        let mut pressed_buttons = HashSet::new();
+        let mut divergence = None;
+        for (frame, input) in expand_inputs(&self.inputs, 0..self.frame)
+            .into_iter()
+            .enumerate()
+        {
+            if let Some(snapshot) = self.history.get_exact(frame) {
+                if self.game.save() != *snapshot {
+                    divergence = Some(frame);
+                    break;
                 }
-                geng::Event::MouseUp { button, .. } => {
-                    self.pressed_buttons.remove(button);
+            }
+            for event in input.inputs {
+                apply_pressed(&event, &mut pressed_keys, &mut pressed_buttons);
+                if self.syncs_window() {
+                    self.geng().window().set_pressed_keys(pressed_keys.clone());
+                    self.geng()
+                        .window()
+                        .set_pressed_buttons(pressed_buttons.clone());
                 }
-                _ => {}
+                self.game.handle_event(event);
             }
-            // Sync pressed states
-            self.geng
+            self.game
+                .update(input.delta_time.unwrap_or(self.fixed_delta_time));
+            self.game.fixed_update(self.fixed_delta_time);
+        }
+        if divergence.is_none() && self.game.save() != current {
+            divergence = Some(self.frame);
+        }
+
+        self.game.load(current);
+        if self.syncs_window() {
+            self.geng()
                 .window()
                 .set_pressed_keys(self.pressed_keys.clone());
-            self.geng
+            self.geng()
                 .window()
                 .set_pressed_buttons(self.pressed_buttons.clone());
+        }
+        divergence
+    }
+}
+
+impl<T: Tasable> Tas<T> {
+    fn save_savestates(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = &self.config.savestates_dir {
+            return self.save_savestates_dir(dir);
+        }
+        std::fs::write("savedstates.json", self.save_states_to_bytes()?)?;
+        Ok(())
+    }
 
-            self.game.handle_event(input.clone());
+    /// Writes every save state into its own file in the directory,
+    /// see [TasConfig::savestates_dir].
+    fn save_savestates_dir(&self, dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::path::Path::new(dir);
+        std::fs::create_dir_all(dir)?;
+        for (index, state) in self.saved_states.iter().enumerate() {
+            let mut encoder = DeltaEncoder::new(self.config.delta_savestates);
+            let stored = state.try_map(|saved| encoder.store::<T>(saved))?;
+            std::fs::write(
+                dir.join(format!("state_{index}.json")),
+                to_json(&stored, self.config.compact_json)?,
+            )?;
         }
 
-        // Update inputs
-        if let Some(replay) = &mut self.replay {
-            if replay.frame < replay.states.len() {
-                // Get next input
-                replay.next_input = replay.next_input.saturating_sub(1);
-                if replay.next_input == 0 {
-                    replay.input += 1;
-                    if let Some(next) = replay.inputs.get(replay.input) {
-                        replay.next_input = next.frames;
-                    }
-                }
-
-                // Next state
-                self.game.load(replay.states[replay.frame].clone());
-
-                replay.frame += 1;
-            }
-        } else {
-            // Record the inputs
-            let inputs = std::mem::take(&mut self.queued_inputs);
-            if let Some(last) = self.inputs.last_mut().filter(|last| last.inputs == inputs) {
-                // Extend last input
-                last.frames += 1;
-            } else {
-                // Create new input
-                self.inputs.push(FrameInput { frames: 1, inputs });
+        // Remove the files of the states that have been deleted
+        for index in self.saved_states.len().. {
+            let path = dir.join(format!("state_{index}.json"));
+            if !path.exists() {
+                break;
             }
-
-            self.states.push(self.game.save());
+            std::fs::remove_file(path)?;
         }
+        Ok(())
+    }
 
-        // Update
-        self.game.update(self.fixed_delta_time);
-        self.game.fixed_update(self.fixed_delta_time);
+    /// Serializes all save states into memory, e.g. for platforms without a filesystem.
+    pub fn save_states_to_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut encoder = DeltaEncoder::new(self.config.delta_savestates);
+        let stored = self
+            .saved_states
+            .iter()
+            .map(|state| state.try_map(|saved| encoder.store::<T>(saved)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(to_json(&stored, self.config.compact_json)?)
+    }
 
-        self.frame += 1;
+    /// Writes the save states to disk if they have changed since the last write.
+    pub fn flush_savestates(&mut self) {
+        self.states_flush_timer = 0.0;
+        if !self.states_dirty {
+            return;
+        }
+        self.states_dirty = false;
+        if let Err(err) = self.save_savestates() {
+            log_error!("Failed to save states: {err}");
+        }
+    }
+}
+
+impl<T: Tasable> Drop for Tas<T> {
+    fn drop(&mut self) {
+        self.flush_savestates();
     }
 }
 
 impl<T: geng::State + Tasable> geng::State for Tas<T> {
     fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
         self.framebuffer_size = framebuffer.size();
+
+        if self.comparison.is_some() {
+            self.draw_comparison(framebuffer);
+            if !self.ui_disabled {
+                self.draw_preview(framebuffer);
+            }
+            return;
+        }
+
+        if self.interpolating() && !self.paused && !self.menu_open {
+            if let Some(previous) = &self.previous_state {
+                // Draw in between the previous and the current frame
+                let alpha = (self.clock.time() / self.fixed_delta_time).clamp(0.0, 1.0);
+                if let Some(mut interpolated) = self.game.interpolate(previous, alpha) {
+                    interpolated.draw(framebuffer);
+                    return;
+                }
+            }
+        }
+
         self.game.draw(framebuffer);
+        self.hash_render(framebuffer);
+        if !self.ui_disabled {
+            self.draw_preview(framebuffer);
+        }
     }
 
-    fn update(&mut self, _delta_time: f64) {}
+    fn update(&mut self, delta_time: f64) {
+        self.update_toasts(delta_time);
+        self.pacing_time += delta_time;
+        self.frame_time += delta_time;
+
+        // Frames simulated in between two updates beyond the time scale are catching up,
+        // meaning the simulation cannot keep up with real time
+        let frames_per_update = self.time_scale * self.engine_delta_time / self.fixed_delta_time;
+        let expected = frames_per_update.ceil().max(1.0) as usize;
+        let lag = std::mem::take(&mut self.burst_frames).saturating_sub(expected);
+        self.lag_frames += lag;
+        self.lagging = lag > 0;
+
+        if self.config.animate_while_paused && (self.paused || self.menu_open) {
+            self.game.update(delta_time);
+        }
+        if self.config.record_delta_time && !self.paused && !self.menu_open {
+            // Simulate a frame per update with the real delta time
+            if self.replay.is_none() {
+                self.frame_delta_time = Some(delta_time * self.time_scale);
+            }
+            self.advance_frame();
+        }
+        self.states_flush_timer += delta_time;
+        if self.states_flush_timer >= self.config.savestates_flush_interval {
+            self.flush_savestates();
+        }
+        self.title_timer += delta_time;
+        if self.title_timer >= WINDOW_TITLE_INTERVAL {
+            self.title_timer = 0.0;
+            self.update_window_title();
+        }
+    }
 
     fn fixed_update(&mut self, delta_time: f64) {
-        self.fixed_delta_time = delta_time;
-        if !self.paused && !self.auto_paused {
-            let mut sim_time = self.acc_delta_time + delta_time * self.time_scale;
-            while sim_time >= self.fixed_delta_time {
-                sim_time -= self.fixed_delta_time;
-                self.next_frame();
+        self.engine_delta_time = delta_time;
+        self.fixed_delta_time = self
+            .config
+            .frame_rate
+            .filter(|&rate| rate > 0.0)
+            .map_or(delta_time, |rate| 1.0 / rate);
+        if self.paused || self.menu_open {
+            // The leftover time belongs to the moment of pausing,
+            // so resuming always starts from a whole frame boundary
+            self.clock.reset();
+            return;
+        }
+
+        if self.config.record_delta_time {
+            // Frames are simulated in `update` instead
+            return;
+        }
+
+        if self.config.lockstep_recording && self.replay.is_none() {
+            self.clock.reset();
+            self.next_frame();
+            return;
+        }
+
+        // Accumulate scaled time and simulate every whole frame that fits,
+        // the remainder (always less than a frame) carries over to the next update
+        self.clock.advance(delta_time * self.time_scale);
+        while self.clock.take_frame(self.frame_interval()) {
+            self.advance_frame();
+            self.burst_frames += 1;
+            if self.paused {
+                // E.g. a breakpoint has been hit
+                self.clock.reset();
+                break;
             }
-            self.acc_delta_time = sim_time;
         }
     }
 
     fn handle_event(&mut self, event: geng::Event) {
-        if let geng::Event::KeyDown {
-            key: geng::Key::LAlt,
-        } = event
-        {
-            self.auto_paused = true;
+        if !self.simulation.accepts(&event) {
             return;
         }
-        if let geng::Event::KeyUp {
-            key: geng::Key::LAlt,
-        } = event
-        {
-            self.auto_paused = false;
+        if self.text_focused {
+            // The event is handled by the text field
             return;
         }
 
-        if self.auto_paused {
+        let keys = self.config.keybinds.clone();
+        match event {
+            geng::Event::KeyDown { key } if key == keys.menu => {
+                self.menu_open = match self.config.menu_activation {
+                    MenuActivation::Hold => true,
+                    MenuActivation::Toggle => !self.menu_open,
+                };
+                return;
+            }
+            geng::Event::KeyUp { key } if key == keys.menu => {
+                if let MenuActivation::Hold = self.config.menu_activation {
+                    self.menu_open = false;
+                }
+                return;
+            }
+            geng::Event::Focused(false) => {
+                // Any keys released while unfocused would stay held otherwise
+                self.release_all_inputs();
+                return;
+            }
+            geng::Event::KeyDown { key } if self.config.peek_ui && key == keys.peek_ui => {
+                self.ui_peeking = true;
+                return;
+            }
+            geng::Event::KeyUp { key } if self.config.peek_ui && key == keys.peek_ui => {
+                self.ui_peeking = false;
+                return;
+            }
+            geng::Event::KeyDown { key }
+                if key == keys.input_overlay
+                    && self.config.input_strip_frames > 0
+                    && !self.ui_disabled =>
+            {
+                self.show_input_overlay = !self.show_input_overlay;
+                return;
+            }
+            _ => {}
+        }
+
+        if self.menu_open {
             // Capture the event
             if let geng::Event::KeyDown { key } = event {
                 match key {
-                    geng::Key::S => {
-                        self.save_run("tas.json").unwrap();
+                    _ if key == keys.save_run => {
+                        let path = self.save_file.clone();
+                        match self.save_run(&path) {
+                            Ok(()) => self.notify(format!("Run saved to {path}")),
+                            Err(err) => self.notify_error(format!("Failed to save run: {err}")),
+                        }
                     }
-                    geng::Key::R => {
+                    _ if key == keys.backup_run => match self.backup_run() {
+                        Ok(path) => {
+                            log_info!("Run backed up to {path}");
+                            self.notify(format!("Run backed up to {path}"));
+                        }
+                        Err(err) => self.notify_error(format!("Failed to back up the run: {err}")),
+                    },
+                    _ if key == keys.toggle_replay => {
                         // Toggle replay
-                        if let Some(replay) = self.replay.take() {
+                        if self.continue_after_replay {
+                            // Continue the watched recording from here
+                            self.take_over_replay();
+                        } else if let Some(replay) = self.replay.take() {
                             // self.inputs = replay.inputs;
-                            self.states = replay.states;
+                            self.history = replay.history;
+                            self.history.truncate(self.frame);
+                            self.log_dirty = true;
                         } else if let Err(err) = self.load_run(&self.save_file.clone()) {
-                            log::error!("Failed to load run: {err}");
+                            self.notify_error(format!("Failed to load run: {err}"));
                         }
                         self.paused = true;
                     }
-                    geng::Key::K => {
+                    _ if key == keys.save_state => {
                         self.save_state();
                     }
-                    geng::Key::L if !self.saved_states.is_empty() => {
+                    _ if key == keys.load_state && !self.saved_states.is_empty() => {
                         self.load_state(self.selected_state);
                     }
-                    geng::Key::P => {
-                        self.paused = !self.paused;
+                    _ if key == keys.pause => {
+                        if self.paused {
+                            self.paused = false;
+                        } else {
+                            self.pause();
+                        }
+                    }
+                    _ if key == keys.slower => {
+                        self.set_time_scale(self.time_scale - self.config.time_scale_step);
                     }
-                    geng::Key::Left => {
-                        self.time_scale = (self.time_scale - 0.05).max(0.0);
+                    _ if key == keys.slow_motion => {
+                        if self.time_scale == 1.0 {
+                            self.set_time_scale(self.slow_time_scale);
+                        } else {
+                            // Remember the current speed to switch back to it later
+                            self.slow_time_scale = self.time_scale;
+                            self.time_scale = 1.0;
+                        }
                     }
-                    geng::Key::Right => {
-                        self.time_scale += 0.05;
+                    _ if key == keys.faster => {
+                        self.set_time_scale(self.time_scale + self.config.time_scale_step);
                     }
-                    geng::Key::Up if !self.saved_states.is_empty() => {
+                    _ if key == keys.next_state && !self.saved_states.is_empty() => {
                         self.selected_state = (self.selected_state + 1)
                             .min(self.saved_states.len().saturating_sub(1));
                         self.load_state(self.selected_state);
                     }
-                    geng::Key::Down if !self.saved_states.is_empty() => {
+                    _ if key == keys.prev_state && !self.saved_states.is_empty() => {
                         self.selected_state = self.selected_state.saturating_sub(1);
                         self.load_state(self.selected_state);
                     }
+                    _ if key == keys.cycle_state && !self.saved_states.is_empty() => {
+                        let window = self.geng().window();
+                        let back = window.is_key_pressed(geng::Key::LShift)
+                            || window.is_key_pressed(geng::Key::RShift);
+                        let len = self.saved_states.len();
+                        self.selected_state = if back {
+                            (self.selected_state + len - 1) % len
+                        } else {
+                            (self.selected_state + 1) % len
+                        };
+                        self.load_state(self.selected_state);
+                    }
+                    _ if key == keys.undo_delete => {
+                        self.undo_delete_state();
+                    }
+                    _ if key == keys.help => {
+                        self.show_help = !self.show_help;
+                    }
+                    _ if key == keys.log_view => {
+                        self.show_log = !self.show_log;
+                    }
+                    _ if key == keys.next_state_frame => {
+                        self.seek_next_state();
+                    }
+                    _ if key == keys.prev_state_frame => {
+                        self.seek_prev_state();
+                    }
+                    _ if key == keys.reverse => {
+                        self.set_reverse(!self.reversing);
+                    }
+                    _ if key == keys.restart => {
+                        self.restart();
+                    }
+                    _ if key == keys.console && self.config.enable_console => {
+                        self.console_open = !self.console_open;
+                    }
+                    _ if key == keys.take_over => {
+                        self.take_over_replay();
+                    }
+                    _ if key == keys.fork => {
+                        self.fork_replay();
+                    }
+                    _ if key == keys.step => {
+                        self.step(1);
+                    }
+                    _ if key == keys.record_macro => {
+                        self.toggle_macro_recording();
+                    }
+                    _ if key == keys.play_macro => {
+                        if let Some(name) = self.selected_macro.clone() {
+                            self.play_macro(&name);
+                        }
+                    }
                     _ => {}
                 }
             }
-            return;
-        }
 
-        if self.replay.is_some() {
-            return;
+            // Keys not bound to any action are recorded,
+            // so that they can be held while advancing frame by frame
+            let recorded = match event {
+                geng::Event::KeyDown { key } | geng::Event::KeyUp { key } => {
+                    keys.records_in_menu(key)
+                }
+                _ => false,
+            };
+            if !recorded {
+                return;
+            }
         }
 
-        self.queued_inputs.push(event);
+        self.queue_event(event);
     }
 
     fn ui<'a>(&'a mut self, cx: &'a geng::ui::Controller) -> Box<dyn geng::ui::Widget + 'a> {
-        if !self.show_ui {
+        if self.ui_disabled {
             return self.game.ui(cx);
         }
+        let peek_hidden = self.config.peek_ui && !self.ui_peeking && !self.menu_open;
+        if !self.show_ui || peek_hidden {
+            // The input overlay is toggled separately, e.g. to capture a video with only it shown
+            return match self.input_overlay() {
+                Some(overlay) => Box::new(geng::ui::stack(vec![
+                    self.game.ui(cx),
+                    overlay.align(vec2(0.0, 0.6)).boxed(),
+                ])),
+                None => self.game.ui(cx),
+            };
+        }
 
         use geng::ui::{column, *};
 
         let framebuffer_size = self.framebuffer_size.map(|x| x as f32);
         let text_size = framebuffer_size.y * 0.05;
 
-        let font = self.geng.default_font().clone();
+        let geng = self.geng().clone();
+        let font = geng.default_font().clone();
         let slider = move |name, range, value: &mut f64, text_size| {
             ui::slider(cx, name, value, range, font.clone(), text_size)
         };
 
-        let font = self.geng.default_font().clone();
+        let font = geng.default_font().clone();
         let text =
             move |text, text_size| geng::ui::Text::new(text, font.clone(), text_size, Rgba::WHITE);
 
@@ -423,13 +3244,30 @@ impl<T: geng::State + Tasable> geng::State for Tas<T> {
 
         let mut load_state = None;
         let mut delete_state = None;
+        let mut diff_state = None;
         let mut saved_states: Vec<_> = self
             .saved_states
             .iter()
             .enumerate()
-            .map(|(i, _)| {
+            .map(|(i, state)| {
+                let mut label = match &state.name {
+                    Some(name) => format!("{name} — frame {}", state.frame),
+                    None => format!("Save #{i} — frame {}", state.frame),
+                };
+                if let Some(checkpoint) = &state.label {
+                    label += &format!(" ({checkpoint})");
+                }
+                if !state.description.is_empty() {
+                    label += &format!(": {}", state.description);
+                }
+                label += &format!(" [{}]", format_size(state.size));
+                let color = if self.loaded_state == Some(i) {
+                    Rgba::YELLOW
+                } else {
+                    Rgba::WHITE
+                };
                 row![
-                    text(format!("Save #{i}"), text_size,),
+                    geng::ui::Text::new(label, geng.default_font().clone(), text_size, color),
                     button!("Load" => {
                         load_state = Some(i);
                     })
@@ -438,60 +3276,658 @@ impl<T: geng::State + Tasable> geng::State for Tas<T> {
                             delete_state = Some(i);
                     })
                     .padding_horizontal(20.0),
+                    button!(if self.diff_base == Some(i) { "Diff..." } else { "Diff" } => {
+                        diff_state = Some(i);
+                    })
+                    .padding_horizontal(20.0),
                 ]
                 .padding_vertical(10.0)
                 .boxed()
             })
             .collect();
+        if let Some(i) = diff_state {
+            match self.diff_base.take() {
+                Some(base) if base != i => {
+                    self.state_diff = self.diff_states(base, i);
+                    if self.state_diff.is_empty() {
+                        self.state_diff.push("The states are identical".to_string());
+                    }
+                }
+                Some(_) => self.state_diff.clear(),
+                None => self.diff_base = Some(i),
+            }
+        }
         if let Some(i) = delete_state {
-            self.saved_states.remove(i);
+            self.delete_state(i);
         } else if let Some(i) = load_state {
             self.load_state(i);
         }
 
+        let length = self.total_frames();
+        let markers = self.saved_states.iter().map(|state| state.frame).collect();
+        let timeline = ui::Timeline::new(cx, self.frame, length, markers);
+        if let Some(frame) = timeline.get_change() {
+            self.seek(frame);
+        }
+        self.preview_frame = timeline.get_hover();
+
+        // Edit the events of the last simulated frame
+        let edit_frame = self.frame.checked_sub(1);
+        let mut remove_event = None;
+        let frame_events = edit_frame
+            .map(|frame| self.frame_events(frame))
+            .unwrap_or_default();
+        let mut frame_editor: Vec<_> = frame_events
+            .iter()
+            .enumerate()
+            .map(|(i, event)| {
+                row![
+                    text(format!("{event:?}"), text_size * 0.5),
+                    button!("Remove" => {
+                        remove_event = Some(i);
+                    })
+                    .padding_horizontal(20.0),
+                ]
+                .boxed()
+            })
+            .collect();
+        if let (Some(frame), Some(i)) = (edit_frame, remove_event) {
+            let mut events = frame_events.clone();
+            events.remove(i);
+            self.set_frame_inputs(frame, events);
+        }
+        frame_editor.push(
+            button!("Cut the run here" => {
+                self.truncate_at_current();
+            })
+            .boxed(),
+        );
+        frame_editor.insert(
+            0,
+            text(
+                match edit_frame {
+                    Some(frame) => format!("Frame {frame} events"),
+                    None => "No frames yet".to_string(),
+                },
+                text_size * 0.5,
+            )
+            .boxed(),
+        );
+
+        let help =
+            self.show_help.then(|| {
+                let keys = &self.config.keybinds;
+                let menu = match self.config.menu_activation {
+                    MenuActivation::Hold => format!("Hold {:?} to open the menu", keys.menu),
+                    MenuActivation::Toggle => format!("Press {:?} to toggle the menu", keys.menu),
+                };
+                let mut lines = vec![text(menu, text_size * 0.5).boxed()];
+                lines.extend(keys.list().into_iter().map(|(key, action)| {
+                    text(format!("{key:?}: {action}"), text_size * 0.5).boxed()
+                }));
+                column(lines)
+            });
+
+        let log = self.show_log.then(|| {
+            let lines = logs::recent(10)
+                .into_iter()
+                .map(|line| text(line, text_size * 0.4).boxed())
+                .collect();
+            column(lines)
+        });
+
+        let state_diff = (!self.state_diff.is_empty()).then(|| {
+            let mut lines: Vec<_> = self
+                .state_diff
+                .iter()
+                .take(10)
+                .map(|line| text(line.clone(), text_size * 0.4).boxed())
+                .collect();
+            lines.push(
+                button!("Close diff" => {
+                    self.state_diff.clear();
+                })
+                .boxed(),
+            );
+            column(lines)
+        });
+
+        let density = ui::BarGraph::new(cx, self.input_density(100));
+
+        let input_strip = self.input_overlay();
+
+        let mut play_macro = None;
+        let mut delete_macro = None;
+        let mut macro_names: Vec<_> = self.macros.keys().cloned().collect();
+        macro_names.sort();
+        let mut macros: Vec<_> = macro_names
+            .into_iter()
+            .map(|name| {
+                row![
+                    text(name.clone(), text_size),
+                    button!("Play" => {
+                        play_macro = Some(name.clone());
+                    })
+                    .padding_horizontal(20.0),
+                    button!("Delete" => {
+                        delete_macro = Some(name.clone());
+                    })
+                    .padding_horizontal(20.0),
+                ]
+                .padding_vertical(10.0)
+                .boxed()
+            })
+            .collect();
+        if let Some(name) = delete_macro {
+            self.delete_macro(&name);
+        } else if let Some(name) = play_macro {
+            self.play_macro(&name);
+        }
+
+        let (save_file_input, response) =
+            ui::text_input(cx, &self.save_file, geng.default_font().clone(), text_size);
+        self.text_focused = response.focused;
+        if let Some(save_file) = response.change {
+            self.save_file = save_file;
+        }
+
+        let (goto_input, response) = ui::text_input(
+            cx,
+            &self.goto_input,
+            geng.default_font().clone(),
+            text_size * 0.5,
+        );
+        self.text_focused |= response.focused;
+        if let Some(input) = response.change {
+            self.goto_input = input;
+        }
+        if let Some(input) = response.submit {
+            match input.trim().parse::<usize>() {
+                Ok(frame) => {
+                    self.goto_input.clear();
+                    // Seeking clamps the frame to the length of the run
+                    self.seek(frame);
+                }
+                Err(_) => self.notify(format!("Not a frame number: {input:?}")),
+            }
+        }
+        let (goto_time_input, response) = ui::text_input(
+            cx,
+            &self.goto_time_input,
+            geng.default_font().clone(),
+            text_size * 0.5,
+        );
+        self.text_focused |= response.focused;
+        if let Some(input) = response.change {
+            self.goto_time_input = input;
+        }
+        if let Some(input) = response.submit {
+            match parse_time(&input) {
+                Some(seconds) => {
+                    self.goto_time_input.clear();
+                    self.seek_to_time(seconds);
+                }
+                None => self.notify(format!("Not a time (m:ss.ss): {input:?}")),
+            }
+        }
+        let goto = row![
+            text("Go to frame: ".to_string(), text_size * 0.5),
+            goto_input,
+            text(
+                format!(
+                    " or time ({}): ",
+                    format_time(self.frame as f64 * self.fixed_delta_time)
+                ),
+                text_size * 0.5
+            ),
+            goto_time_input,
+            button!("Previous state" => {
+                self.seek_prev_state();
+            })
+            .padding_horizontal(20.0),
+            button!("Next state" => {
+                self.seek_next_state();
+            }),
+        ];
+
+        let (comment_input, response) = ui::text_input(
+            cx,
+            &self.comment_input,
+            geng.default_font().clone(),
+            text_size * 0.5,
+        );
+        self.text_focused |= response.focused;
+        if let Some(input) = response.change {
+            self.comment_input = input;
+        }
+        if let Some(input) = response.submit {
+            match self.add_comment_from_input(&input) {
+                Ok(()) => self.comment_input.clear(),
+                Err(err) => self.notify(err),
+            }
+        }
+        let comment = row![
+            text("Comment: ".to_string(), text_size * 0.5),
+            comment_input
+        ];
+
+        // The comments annotating the current frame
+        let mut remove_comment = None;
+        let active_comments: Vec<_> = self
+            .active_comments()
+            .map(|i| {
+                let (range, comment) = &self.comments[i];
+                row![
+                    text(
+                        format!("{}..{}: {comment}", range.start, range.end),
+                        text_size * 0.5
+                    ),
+                    button!("Remove" => {
+                        remove_comment = Some(i);
+                    })
+                    .padding_horizontal(20.0),
+                ]
+                .boxed()
+            })
+            .collect();
+        if let Some(i) = remove_comment {
+            self.remove_comment(i);
+        }
+
+        let console = self.config.enable_console && self.console_open;
+        let console = console.then(|| {
+            let (input, response) = ui::text_input(
+                cx,
+                &self.console_input,
+                geng.default_font().clone(),
+                text_size * 0.5,
+            );
+            self.text_focused |= response.focused;
+            if let Some(input) = response.change {
+                self.console_input = input;
+            }
+            if let Some(command) = response.submit {
+                self.console_input.clear();
+                self.run_console_command(&command);
+            }
+            let mut lines: Vec<_> = self
+                .console_log
+                .iter()
+                .map(|line| text(line.clone(), text_size * 0.5).boxed())
+                .collect();
+            lines.push(input.boxed());
+            column(lines)
+        });
+
+        let mut select_run = None;
+        let mut run_files: Vec<_> = self
+            .run_files
+            .iter()
+            .map(|path| {
+                button!(path => {
+                    select_run = Some(path.clone());
+                })
+                .boxed()
+            })
+            .collect();
+        if let Some(path) = select_run {
+            self.save_file = path;
+        }
+
+        let toasts = column(
+            self.toasts
+                .iter()
+                .map(|toast| {
+                    geng::ui::Text::new(
+                        toast.text.clone(),
+                        geng.default_font().clone(),
+                        text_size * 0.5,
+                        Rgba::new(1.0, 1.0, 1.0, toast.alpha()),
+                    )
+                    .boxed()
+                })
+                .collect(),
+        );
+
         let tas_ui = stack![
             text(
                 if self.paused {
                     "Paused".to_string()
                 } else if let Some(replay) = &self.replay {
-                    format!("Replay frame {}", replay.frame)
+                    format!(
+                        "Replay frame {} (input ignored, {:?} to take over)",
+                        replay.frame, self.config.keybinds.take_over
+                    )
                 } else {
-                    "Recording".to_string()
+                    format!(
+                        "Recording {:?} ({} rerecords)",
+                        self.recording_slot, self.rerecords
+                    )
                 },
                 text_size
             )
             .align(vec2(1.0, 0.9)),
-            slider("Time scale", 0.0..=10.0, &mut self.time_scale, text_size).align(vec2(0.5, 1.0)),
+            text(
+                match self.reference_match {
+                    Some(true) => "Reference: match",
+                    Some(false) => "Reference: mismatch",
+                    None => "",
+                }
+                .to_string(),
+                text_size * 0.5
+            )
+            .align(vec2(1.0, 0.85)),
+            text(
+                if self.lag_frames == 0 {
+                    String::new()
+                } else if self.lagging {
+                    format!("Lagging! {} lag frames", self.lag_frames)
+                } else {
+                    format!("{} lag frames", self.lag_frames)
+                },
+                text_size * 0.5
+            )
+            .align(vec2(1.0, 0.8)),
+            geng::ui::Text::new(
+                match self.goal_frame {
+                    Some(goal) if self.frame <= goal => {
+                        format!("Goal: frame {goal} ({} under)", goal - self.frame)
+                    }
+                    Some(goal) => format!("Goal: frame {goal} ({} over)", self.frame - goal),
+                    None => String::new(),
+                },
+                geng.default_font().clone(),
+                text_size * 0.5,
+                if self.goal_frame.map_or(false, |goal| self.frame > goal) {
+                    Rgba::RED
+                } else {
+                    Rgba::WHITE
+                },
+            )
+            .align(vec2(1.0, 0.75)),
+            text(
+                match self.loaded_state.and_then(|i| Some((i, self.saved_states.get(i)?))) {
+                    Some((i, state)) => format!(
+                        "Loaded state #{i}{}",
+                        state.name.as_ref().map_or(String::new(), |name| format!(": {name}"))
+                    ),
+                    None => String::new(),
+                },
+                text_size * 0.5
+            )
+            .align(vec2(1.0, 0.7)),
+            text(
+                match &self.comparison {
+                    Some(comparison) => format!(
+                        "Left: frame {}/{}, right: frame {}/{}",
+                        self.frame,
+                        self.total_frames(),
+                        comparison.frame,
+                        comparison.total_frames
+                    ),
+                    None => String::new(),
+                },
+                text_size * 0.5
+            )
+            .align(vec2(0.5, 0.9)),
+            slider("Time scale", 0.0..=self.config.max_time_scale, &mut self.time_scale, text_size).align(vec2(0.5, 1.0)),
+            timeline
+                .fixed_size(vec2(framebuffer_size.x * 0.6, text_size * 0.5).map(f64::from))
+                .align(vec2(0.5, 0.8)),
+            density
+                .fixed_size(vec2(framebuffer_size.x * 0.6, text_size).map(f64::from))
+                .align(vec2(0.5, 0.72)),
+            column({
+                run_files.push(
+                    button!("Refresh" => {
+                        self.refresh_run_files();
+                    })
+                    .boxed(),
+                );
+                run_files
+            })
+            .align(vec2(0.0, 0.3)),
+            column(frame_editor).align(vec2(0.5, 0.3)),
+            column![goto, comment].align(vec2(0.5, 0.65)),
+            column(active_comments).align(vec2(0.5, 0.58)),
+            console.align(vec2(0.5, 0.5)),
+            help.align(vec2(0.5, 0.5)),
+            log.align(vec2(0.0, 1.0)),
+            state_diff.align(vec2(1.0, 0.6)),
+            input_strip.align(vec2(0.0, 0.6)),
+            toasts.align(vec2(1.0, 1.0)),
             column![
-                text(self.save_file.clone(), text_size),
+                save_file_input,
                 row![
                     button!("Save run" => {
-                        if let Err(err) = self.save_run(&self.save_file) {
-                            log::error!("Failed to save run: {err}");
+                        let path = self.save_file.clone();
+                        match self.save_run(&path) {
+                            Ok(()) => self.notify(format!("Run saved to {path}")),
+                            Err(err) => self.notify_error(format!("Failed to save run: {err}")),
                         }
                     }),
+                    button!("Watch recording" => {
+                        self.watch_recording();
+                    }),
                     button!("Start replay" => {
                         if let Err(err) = self.load_run(&self.save_file.clone()) {
-                            log::error!("Failed to load run: {err}");
+                            self.notify_error(format!("Failed to load run: {err}"));
+                        }
+                    }),
+                    button!(if self.reversing { "Play forwards" } else { "Play backwards" } => {
+                        self.set_reverse(!self.reversing);
+                    }),
+                    button!(if self.config.sync_pressed_state { "Window sync: on" } else { "Window sync: off" } => {
+                        self.config.sync_pressed_state = !self.config.sync_pressed_state;
+                    }),
+                    button!(if self.reference.is_some() { "Clear reference" } else { "Compare with" } => {
+                        if self.reference.is_some() {
+                            self.clear_reference();
+                        } else if let Err(err) = self.load_reference(&self.save_file.clone()) {
+                            self.notify_error(format!("Failed to load reference run: {err}"));
                         }
                     }),
                 ]
             ]
             .align(vec2(0.0, 0.0)),
             column({
+                saved_states.insert(
+                    0,
+                    text(
+                        format!("Save states: {}", format_size(self.save_states_memory())),
+                        text_size * 0.5,
+                    )
+                    .boxed(),
+                );
                 saved_states.push(
                     button!("Save state" => {
                         self.save_state();
                     })
                     .boxed(),
                 );
+                if !self.deleted_states.is_empty() {
+                    saved_states.push(
+                        button!("Undo delete" => {
+                            self.undo_delete_state();
+                        })
+                        .boxed(),
+                    );
+                }
                 saved_states
             })
             .align(vec2(1.0, 0.0))
-            .padding_bottom(200.0)
+            .padding_bottom(200.0),
+            column({
+                let label = if self.macro_recording.is_some() {
+                    "Stop macro"
+                } else {
+                    "Record macro"
+                };
+                macros.push(
+                    button!(label => {
+                        self.toggle_macro_recording();
+                    })
+                    .boxed(),
+                );
+                macros
+            })
+            .align(vec2(0.0, 0.6))
         ]
         .uniform_padding(30.0);
 
         Box::new(stack(vec![self.game.ui(cx), Box::new(tas_ui)]))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn save_state<T: Clone>(state: T, pressed_keys: HashSet<geng::Key>) -> SaveState<T> {
+        SaveState {
+            name: None,
+            label: None,
+            description: String::new(),
+            auto: false,
+            frame: 0,
+            framebuffer_size: None,
+            state: Some(state.clone()),
+            history: History::new(1, state),
+            inputs: Vec::new(),
+            pressed_keys,
+            pressed_buttons: HashSet::new(),
+            cursor: None,
+            extra: serde_json::Value::Null,
+            replay: false,
+            rng: Vec::new(),
+            version: 0,
+            size: 0,
+        }
+    }
+
+    /// Counts the frames simulated and the keys pressed.
+    #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+    struct Counter {
+        frames: usize,
+        presses: usize,
+    }
+
+    impl geng::State for Counter {
+        fn update(&mut self, _delta_time: f64) {
+            self.frames += 1;
+        }
+
+        fn handle_event(&mut self, event: geng::Event) {
+            if let geng::Event::KeyDown { .. } = event {
+                self.presses += 1;
+            }
+        }
+
+        fn draw(&mut self, _framebuffer: &mut ugli::Framebuffer) {}
+    }
+
+    impl Tasable for Counter {
+        type Saved = Self;
+
+        fn save(&self) -> Self {
+            self.clone()
+        }
+
+        fn load(&mut self, state: Self) {
+            *self = state;
+        }
+    }
+
+    fn press(key: geng::Key) -> geng::Event {
+        geng::Event::KeyDown { key }
+    }
+
+    #[test]
+    fn save_state_keeps_own_run() {
+        let path = std::env::temp_dir().join("geng_tas_save_state_keeps_own_run.json");
+        let config = TasConfig {
+            snapshot_before_load: false,
+            ..default()
+        };
+
+        let mut other = Tas::headless(Counter::default(), config.clone());
+        other.queue_event(press(geng::Key::B));
+        other.step(3);
+        other.save_run(&path).unwrap();
+
+        // A state saved while recording, then one saved while replaying the other run
+        let mut tas = Tas::headless(Counter::default(), config);
+        tas.queue_event(press(geng::Key::A));
+        tas.step(2);
+        tas.save_state();
+        tas.load_run(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        tas.step(1);
+        tas.save_state();
+
+        // Each state restores the run it was saved in
+        tas.load_state(0);
+        assert!(tas.replay.is_none());
+        assert_eq!(tas.frame, 2);
+        assert_eq!(
+            tas.game,
+            Counter {
+                frames: 2,
+                presses: 1
+            }
+        );
+        assert_eq!(
+            frame_inputs(tas.inputs(), 0..2),
+            vec![vec![press(geng::Key::A)], Vec::new()]
+        );
+
+        tas.load_state(1);
+        assert!(tas.replay.is_some());
+        assert_eq!(tas.frame, 1);
+        assert_eq!(
+            tas.game,
+            Counter {
+                frames: 1,
+                presses: 1
+            }
+        );
+        assert_eq!(
+            frame_inputs(tas.inputs(), 0..3),
+            vec![vec![press(geng::Key::B)], Vec::new(), Vec::new()]
+        );
+    }
+
+    #[test]
+    fn key_repeats_not_recorded() {
+        let mut tas = Tas::headless(Counter::default(), TasConfig::default());
+        // Repeats arrive both within the frame of the press and on the following frames
+        for _ in 0..3 {
+            tas.queue_event(press(geng::Key::A));
+        }
+        assert_eq!(tas.queued_inputs, vec![press(geng::Key::A)]);
+        tas.step(1);
+        tas.queue_event(press(geng::Key::A));
+        assert!(tas.queued_inputs.is_empty());
+        assert_eq!(tas.game.presses, 1);
+    }
+
+    #[test]
+    fn load_state_drops_queued_inputs() {
+        let state = save_state((), [geng::Key::A].into_iter().collect());
+        let mut pressed_keys = HashSet::new();
+        let mut pressed_buttons = HashSet::new();
+        let mut queued_inputs = vec![geng::Event::KeyDown { key: geng::Key::B }];
+        let mut queued_timestamps = vec![0.0];
+        state.restore_input(
+            &mut pressed_keys,
+            &mut pressed_buttons,
+            &mut queued_inputs,
+            &mut queued_timestamps,
+        );
+
+        // Nothing is left to apply on the next frame
+        assert!(queued_inputs.is_empty());
+        assert!(queued_timestamps.is_empty());
+        assert_eq!(pressed_keys, [geng::Key::A].into_iter().collect());
+    }
+}
@@ -0,0 +1,58 @@
+//! The crate's own log messages, kept to be shown in the overlay ([Keybinds::log_view])
+//! in addition to being sent to the global logger.
+
+use super::*;
+
+use std::sync::Mutex;
+
+/// The maximum number of log messages kept.
+const MAX_LOG_MESSAGES: usize = 50;
+
+static MESSAGES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Keeps the message to be shown in the overlay, dropping the oldest one if needed.
+pub(crate) fn record(level: log::Level, message: String) {
+    let Ok(mut messages) = MESSAGES.lock() else {
+        return;
+    };
+    if messages.len() >= MAX_LOG_MESSAGES {
+        messages.pop_front();
+    }
+    messages.push_back(format!("[{level}] {message}"));
+}
+
+/// The most recent log messages, oldest first.
+pub(crate) fn recent(count: usize) -> Vec<String> {
+    let Ok(messages) = MESSAGES.lock() else {
+        return Vec::new();
+    };
+    let skip = messages.len().saturating_sub(count);
+    messages.iter().skip(skip).cloned().collect()
+}
+
+/// Logs the message with the given level and keeps it for the overlay.
+macro_rules! tas_log {
+    ($level:expr, $($arg:tt)+) => {{
+        let message = format!($($arg)+);
+        log::log!($level, "{message}");
+        crate::logs::record($level, message);
+    }};
+}
+
+macro_rules! log_info {
+    ($($arg:tt)+) => {
+        tas_log!(log::Level::Info, $($arg)+)
+    };
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)+) => {
+        tas_log!(log::Level::Warn, $($arg)+)
+    };
+}
+
+macro_rules! log_error {
+    ($($arg:tt)+) => {
+        tas_log!(log::Level::Error, $($arg)+)
+    };
+}
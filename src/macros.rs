@@ -0,0 +1,101 @@
+use super::*;
+
+const MACROS_FILE: &str = "macros.json";
+
+/// A macro that is currently being recorded.
+pub struct MacroRecording {
+    pub name: String,
+    /// The frame at which the recording started.
+    pub start: usize,
+}
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Starts recording a macro from the current frame.
+    /// The macro is saved once [Self::stop_macro] is called.
+    pub fn start_macro(&mut self, name: impl Into<String>) {
+        self.macro_recording = Some(MacroRecording {
+            name: name.into(),
+            start: self.frame,
+        });
+    }
+
+    /// Stops recording the macro and saves the inputs recorded since it was started.
+    pub fn stop_macro(&mut self) {
+        let Some(recording) = self.macro_recording.take() else {
+            return;
+        };
+        if self.replay.is_some() || recording.start > self.frame {
+            log_warn!(
+                "Macro {} was interrupted and has not been saved",
+                recording.name
+            );
+            return;
+        }
+
+        let inputs = compress_inputs(frame_inputs(&self.inputs, recording.start..self.frame));
+        self.selected_macro = Some(recording.name.clone());
+        self.macros.insert(recording.name, inputs);
+        if let Err(err) = self.save_macros() {
+            log_error!("Failed to save macros: {err}");
+        }
+    }
+
+    /// Queues the inputs of the macro to be recorded over the next frames.
+    pub fn play_macro(&mut self, name: &str) {
+        let Some(inputs) = self.macros.get(name) else {
+            log_warn!("Macro {name} does not exist");
+            return;
+        };
+        self.pending_macro = frame_inputs(inputs, 0..total_frames(inputs)).into();
+        self.selected_macro = Some(name.to_owned());
+    }
+
+    pub fn delete_macro(&mut self, name: &str) {
+        if self.macros.remove(name).is_none() {
+            return;
+        }
+        if self.selected_macro.as_deref() == Some(name) {
+            self.selected_macro = None;
+        }
+        if let Err(err) = self.save_macros() {
+            log_error!("Failed to save macros: {err}");
+        }
+    }
+
+    /// Starts recording a new macro with a generated name, or stops the current recording.
+    pub(crate) fn toggle_macro_recording(&mut self) {
+        if self.macro_recording.is_some() {
+            self.stop_macro();
+            return;
+        }
+        let name = (self.macros.len()..)
+            .map(|i| format!("Macro #{i}"))
+            .find(|name| !self.macros.contains_key(name))
+            .unwrap();
+        self.start_macro(name);
+    }
+
+    fn save_macros(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let macros: HashMap<_, _> = self
+            .macros
+            .iter()
+            .map(|(name, inputs)| (name, to_stable(inputs)))
+            .collect();
+        std::fs::write(MACROS_FILE, to_json(&macros, self.config.compact_json)?)?;
+        Ok(())
+    }
+
+    pub(crate) fn load_macros(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Ok(file) = std::fs::File::open(MACROS_FILE) else {
+            self.macros = default();
+            return Ok(());
+        };
+        let reader = std::io::BufReader::new(file);
+        let macros: HashMap<String, Vec<FrameInput<AnyEvent>>> = serde_json::from_reader(reader)?;
+        self.macros = macros
+            .into_iter()
+            .map(|(name, inputs)| (name, from_any(inputs)))
+            .collect();
+        Ok(())
+    }
+}
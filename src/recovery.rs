@@ -0,0 +1,110 @@
+use super::*;
+
+use std::io::{BufRead, Write};
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Restores the recording from the log left by a previous session, if there is one.
+    pub(crate) fn recover_log(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(path) = self.config.recording_log.clone() else {
+            return Ok(());
+        };
+        let Ok(file) = std::fs::File::open(&path) else {
+            return Ok(());
+        };
+        let mut lines = std::io::BufReader::new(file).lines();
+        let Some(header) = lines.next() else {
+            return Ok(());
+        };
+        let header: StreamHeader = serde_json::from_str(&header?)?;
+        let initial_state = header.restore(&self.game)?;
+
+        let mut inputs = Vec::new();
+        for line in lines {
+            // The last line may have been cut off by a crash
+            let Ok(input) = serde_json::from_str::<FrameInput<AnyEvent>>(&line?) else {
+                break;
+            };
+            inputs.push(input);
+        }
+        let mut inputs = from_any(inputs);
+        merge_inputs(&mut inputs);
+
+        let frames = total_frames(&inputs);
+        log_info!("Recovering {frames} frames from {path}");
+        self.game.load(initial_state.clone());
+        self.history = History::new(self.config.snapshot_interval, initial_state);
+        self.inputs = inputs;
+        self.frame = frames;
+        // Simulate the recovered inputs
+        self.seek(frames);
+        Ok(())
+    }
+
+    /// Appends the last recorded frame to the recording log.
+    /// If the recording has changed in other ways, the whole log is rewritten instead.
+    pub(crate) fn append_log(&mut self) {
+        let Some(path) = &self.config.recording_log else {
+            return;
+        };
+        let result = if self.log_dirty {
+            self.rewrite_log(path)
+        } else {
+            self.append_last_frame(path)
+        };
+        match result {
+            Ok(()) => self.log_dirty = false,
+            Err(err) => {
+                if !self.log_dirty {
+                    log_error!("Failed to write the recording log: {err}");
+                }
+                self.log_dirty = true;
+            }
+        }
+    }
+
+    fn append_last_frame(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(last) = self.inputs.last() else {
+            return Ok(());
+        };
+        let mut file = std::fs::OpenOptions::new().append(true).open(path)?;
+        let frame = FrameInput {
+            frames: 1,
+            ..last.clone()
+        }
+        .filter_map(|event| StableEvent::from_event(&event));
+        writeln!(file, "{}", serde_json::to_string(&frame)?)?;
+        Ok(())
+    }
+
+    pub(crate) fn rewrite_log(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let initial_state = self
+            .history
+            .first()
+            .ok_or("There is no initial state to log")?;
+        let file = std::fs::File::create(path)?;
+        let mut writer = std::io::BufWriter::new(file);
+        let header = StreamHeader::new::<T>(initial_state, self.fixed_delta_time)?;
+        writeln!(writer, "{}", serde_json::to_string(&header)?)?;
+        for input in to_stable(&self.inputs) {
+            writeln!(writer, "{}", serde_json::to_string(&input)?)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Saves the logged recording as a normal run and removes the log.
+    pub fn finalize_log(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_run(path)?;
+        if let Some(log) = &self.config.recording_log {
+            std::fs::remove_file(log)?;
+        }
+        self.log_dirty = true;
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+use super::*;
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Loads a run to compare the inputs being recorded against, frame by frame.
+    pub fn load_reference(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = std::fs::read(path)?;
+        let mut saved = read_run::<T>(&bytes)?;
+        migrate_run(&self.game, &mut saved);
+        self.reference = Some(saved.inputs);
+        self.reference_match = None;
+        Ok(())
+    }
+
+    /// Stops comparing the recorded inputs against the reference run.
+    pub fn clear_reference(&mut self) {
+        self.reference = None;
+        self.reference_match = None;
+    }
+
+    /// Checks whether the currently held keys and buttons
+    /// are the same as in the reference run at the end of the current frame.
+    pub(crate) fn update_reference_match(&mut self) {
+        self.reference_match = self.reference.as_ref().map(|reference| {
+            let (keys, buttons) = pressed_at(reference, self.frame + 1);
+            keys == self.pressed_keys && buttons == self.pressed_buttons
+        });
+    }
+}
@@ -0,0 +1,51 @@
+//! Detecting nondeterministic rendering by comparing hashes of the drawn frames.
+
+use super::*;
+
+use std::hash::Hasher;
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Hashes the frame that has just been drawn, if it is due
+    /// (see [TasConfig::render_hash_interval]). While recording the hash is stored in the run,
+    /// and while replaying it is compared with the recorded one.
+    pub(crate) fn hash_render(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        let Some(interval) = self.config.render_hash_interval else {
+            return;
+        };
+        if self.frame % interval.max(1) != 0 || self.last_hashed_frame == Some(self.frame) {
+            return;
+        }
+        self.last_hashed_frame = Some(self.frame);
+
+        let frame = self.frame;
+        let hash = hash_framebuffer(framebuffer);
+        match &self.replay {
+            Some(replay) => {
+                if let Some(&expected) = replay.render_hashes.get(&frame) {
+                    if expected != hash {
+                        log_warn!("Rendering diverged at frame {frame}");
+                        self.notify(format!("Rendering diverged at frame {frame}"));
+                    }
+                }
+            }
+            None => {
+                self.render_hashes.insert(frame, hash);
+            }
+        }
+    }
+}
+
+fn hash_framebuffer(framebuffer: &mut ugli::Framebuffer) -> u64 {
+    let size = framebuffer.size();
+    let read = framebuffer.read_color();
+    let mut hasher = Fnv1a::default();
+    hasher.write(&(size.x as u64).to_le_bytes());
+    hasher.write(&(size.y as u64).to_le_bytes());
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let color = read.get(x, y);
+            hasher.write(&[color.r, color.g, color.b, color.a]);
+        }
+    }
+    hasher.finish()
+}
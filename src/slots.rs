@@ -0,0 +1,73 @@
+use super::*;
+
+/// A recording stashed away to switch back to later, see [Tas::switch_recording].
+pub(crate) struct RecordingSlot<T> {
+    frame: usize,
+    /// The game state at the current frame.
+    state: T,
+    history: History<T>,
+    inputs: Vec<FrameInput<geng::Event>>,
+    pressed_keys: HashSet<geng::Key>,
+    pressed_buttons: HashSet<geng::MouseButton>,
+    rerecords: usize,
+    render_hashes: BTreeMap<usize, u64>,
+    frame_intervals: Vec<f64>,
+}
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Stashes the current recording under its name and switches to the one with the given name,
+    /// to keep several attempts at a section in memory and compare them.
+    /// A new name starts as a copy of the current recording, e.g. to try another approach
+    /// from the current frame. Recordings cannot be switched during a replay.
+    pub fn switch_recording(&mut self, name: &str) {
+        if name == self.recording_slot {
+            return;
+        }
+        if self.replay.is_some() {
+            self.notify("Cannot switch recordings during a replay");
+            return;
+        }
+
+        let current = RecordingSlot {
+            frame: self.frame,
+            state: self.game.save(),
+            history: self.history.clone(),
+            inputs: self.inputs.clone(),
+            pressed_keys: self.pressed_keys.clone(),
+            pressed_buttons: self.pressed_buttons.clone(),
+            rerecords: self.rerecords,
+            render_hashes: self.render_hashes.clone(),
+            frame_intervals: self.frame_intervals.clone(),
+        };
+        if let Some(slot) = self.recording_slots.remove(name) {
+            self.game.load(slot.state);
+            self.frame = slot.frame;
+            self.history = slot.history;
+            self.inputs = slot.inputs;
+            self.pressed_keys = slot.pressed_keys;
+            self.pressed_buttons = slot.pressed_buttons;
+            self.rerecords = slot.rerecords;
+            self.render_hashes = slot.render_hashes;
+            self.frame_intervals = slot.frame_intervals;
+        }
+        let previous = std::mem::replace(&mut self.recording_slot, name.to_owned());
+        self.recording_slots.insert(previous, current);
+
+        self.queued_inputs.clear();
+        self.queued_timestamps.clear();
+        self.rerecord_reference = None;
+        self.loaded_state = None;
+        self.log_dirty = true;
+        self.notify(format!("Switched to recording {name:?}"));
+    }
+
+    /// The name of the current recording, see [Self::switch_recording].
+    pub fn recording_slot(&self) -> &str {
+        &self.recording_slot
+    }
+
+    /// The names of the stashed recordings.
+    pub fn recording_slots(&self) -> impl Iterator<Item = &str> {
+        self.recording_slots.keys().map(|name| name.as_str())
+    }
+}
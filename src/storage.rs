@@ -0,0 +1,733 @@
+//! Storing game states in files, optionally in a custom format provided by the game.
+
+use super::*;
+
+use base64::Engine;
+
+/// A game state as it is stored in files, see [Tasable::serialize_saved].
+///
+/// States serialized with [Serialize] are stored as they are, like in older files.
+/// The other variants are marked with a key starting with `$`,
+/// which the fields of game structs cannot be named like.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub(crate) enum StoredState {
+    /// The state serialized by the game, encoded in base64.
+    Custom {
+        #[serde(rename = "$tas_custom")]
+        custom: String,
+    },
+    /// The changes to the previously stored state, see [TasConfig::delta_savestates].
+    Delta {
+        #[serde(rename = "$tas_delta")]
+        delta: JsonDelta,
+    },
+    /// The state serialized with its [Serialize] implementation.
+    Serde(serde_json::Value),
+}
+
+impl StoredState {
+    pub(crate) fn store<T: Tasable>(state: &T::Saved) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(match T::serialize_saved(state) {
+            Some(bytes) => Self::Custom {
+                custom: base64::engine::general_purpose::STANDARD.encode(bytes),
+            },
+            None => Self::Serde(serde_json::to_value(state)?),
+        })
+    }
+
+    pub(crate) fn restore<T: Tasable>(&self) -> Result<T::Saved, Box<dyn std::error::Error>> {
+        match self {
+            Self::Custom { custom } => {
+                let bytes = base64::engine::general_purpose::STANDARD.decode(custom)?;
+                T::deserialize_saved(&bytes).ok_or_else(|| "Failed to deserialize a state".into())
+            }
+            Self::Delta { .. } => Err("A state is stored as changes to an unknown state".into()),
+            Self::Serde(value) => Ok(T::Saved::deserialize(value)?),
+        }
+    }
+}
+
+/// Stores a sequence of states, each one as the changes to the previous one, if enabled.
+pub(crate) struct DeltaEncoder {
+    enabled: bool,
+    previous: Option<serde_json::Value>,
+}
+
+impl DeltaEncoder {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            previous: None,
+        }
+    }
+
+    pub(crate) fn store<T: Tasable>(
+        &mut self,
+        state: &T::Saved,
+    ) -> Result<StoredState, Box<dyn std::error::Error>> {
+        let stored = StoredState::store::<T>(state)?;
+        if !self.enabled {
+            return Ok(stored);
+        }
+        let StoredState::Serde(value) = stored else {
+            self.previous = None;
+            return Ok(stored);
+        };
+        let stored = match &self.previous {
+            Some(previous) => StoredState::Delta {
+                delta: json_delta(previous, &value),
+            },
+            None => StoredState::Serde(value.clone()),
+        };
+        self.previous = Some(value);
+        Ok(stored)
+    }
+}
+
+/// Restores a sequence of states stored with a [DeltaEncoder], in the same order.
+#[derive(Default)]
+pub(crate) struct DeltaDecoder {
+    previous: Option<serde_json::Value>,
+}
+
+impl DeltaDecoder {
+    pub(crate) fn restore<T: Tasable>(
+        &mut self,
+        stored: &StoredState,
+    ) -> Result<T::Saved, Box<dyn std::error::Error>> {
+        let value = match stored {
+            StoredState::Custom { .. } => {
+                self.previous = None;
+                return stored.restore::<T>();
+            }
+            StoredState::Delta { delta } => {
+                let mut value = self
+                    .previous
+                    .clone()
+                    .ok_or("A state is stored as changes to an unknown state")?;
+                apply_json_delta(&mut value, delta)?;
+                value
+            }
+            StoredState::Serde(value) => value.clone(),
+        };
+        let state = T::Saved::deserialize(&value)?;
+        self.previous = Some(value);
+        Ok(state)
+    }
+}
+
+/// The changes turning one JSON value into another, see [json_delta].
+///
+/// The variants are marked with keys starting with `$`, so that
+/// changing a field to any value (including `null`) is told apart from removing it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum JsonDelta {
+    /// The value is replaced as a whole.
+    #[serde(rename = "$set")]
+    Set(serde_json::Value),
+    /// The fields of an object are changed (recursively) or removed.
+    #[serde(rename = "$fields")]
+    Fields {
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        changed: BTreeMap<String, JsonDelta>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        removed: Vec<String>,
+    },
+}
+
+/// Computes the changes turning `from` into `to`: changed fields of objects are stored
+/// recursively, and any other changed values as they are.
+fn json_delta(from: &serde_json::Value, to: &serde_json::Value) -> JsonDelta {
+    use serde_json::Value;
+
+    let (Value::Object(from), Value::Object(to)) = (from, to) else {
+        return JsonDelta::Set(to.clone());
+    };
+    let changed = to
+        .iter()
+        .filter_map(|(key, value)| {
+            let delta = match from.get(key) {
+                Some(old) if old == value => return None,
+                Some(old) => json_delta(old, value),
+                None => JsonDelta::Set(value.clone()),
+            };
+            Some((key.clone(), delta))
+        })
+        .collect();
+    let removed = from
+        .keys()
+        .filter(|key| !to.contains_key(key.as_str()))
+        .cloned()
+        .collect();
+    JsonDelta::Fields { changed, removed }
+}
+
+/// Applies the changes computed with [json_delta].
+fn apply_json_delta(
+    value: &mut serde_json::Value,
+    delta: &JsonDelta,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (changed, removed) = match delta {
+        JsonDelta::Set(new) => {
+            *value = new.clone();
+            return Ok(());
+        }
+        JsonDelta::Fields { changed, removed } => (changed, removed),
+    };
+    let serde_json::Value::Object(target) = value else {
+        return Err("The changes to a state do not match the previous state".into());
+    };
+    for key in removed {
+        target.remove(key);
+    }
+    for (key, change) in changed {
+        if let Some(old) = target.get_mut(key) {
+            apply_json_delta(old, change)?;
+            continue;
+        }
+        let JsonDelta::Set(new) = change else {
+            return Err("The changes to a state do not match the previous state".into());
+        };
+        target.insert(key.clone(), new.clone());
+    }
+    Ok(())
+}
+
+/// Serializes bytes in base64, for use with `#[serde(with = "base64_bytes")]`.
+pub(crate) mod base64_bytes {
+    use super::*;
+
+    pub fn serialize<S: serde::Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        base64::engine::general_purpose::STANDARD
+            .encode(bytes)
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(text)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl<T> History<T> {
+    /// Converts every snapshot, stopping at the first error.
+    pub(crate) fn try_map<U, E>(
+        &self,
+        mut f: impl FnMut(&T) -> Result<U, E>,
+    ) -> Result<History<U>, E> {
+        Ok(History {
+            interval: self.interval,
+            states: self.states.iter().map(&mut f).collect::<Result<_, _>>()?,
+            extra: self
+                .extra
+                .iter()
+                .map(|(frame, state)| Ok((*frame, f(state)?)))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl<T> SaveState<T> {
+    /// Converts every game state, stopping at the first error.
+    pub(crate) fn try_map<U, E>(
+        &self,
+        mut f: impl FnMut(&T) -> Result<U, E>,
+    ) -> Result<SaveState<U>, E> {
+        Ok(SaveState {
+            name: self.name.clone(),
+            label: self.label.clone(),
+            description: self.description.clone(),
+            auto: self.auto,
+            frame: self.frame,
+            framebuffer_size: self.framebuffer_size,
+            state: self.state.as_ref().map(&mut f).transpose()?,
+            history: self.history.try_map(f)?,
+            inputs: self.inputs.clone(),
+            pressed_keys: self.pressed_keys.clone(),
+            pressed_buttons: self.pressed_buttons.clone(),
+            cursor: self.cursor,
+            extra: self.extra.clone(),
+            replay: self.replay,
+            rng: self.rng.clone(),
+            version: self.version,
+            size: self.size,
+        })
+    }
+}
+
+impl<T> SavedTas<T> {
+    /// Converts every game state, stopping at the first error.
+    pub(crate) fn try_map<U, E>(
+        &self,
+        f: impl FnMut(&T) -> Result<U, E>,
+    ) -> Result<SavedTas<U>, E> {
+        Ok(SavedTas {
+            header: self.header.clone(),
+            fixed_delta_time: self.fixed_delta_time,
+            framebuffer_size: self.framebuffer_size,
+            rerecords: self.rerecords,
+            total_frames: self.total_frames,
+            input_count: self.input_count,
+            render_hashes: self.render_hashes.clone(),
+            frame_intervals: self.frame_intervals.clone(),
+            comments: self.comments.clone(),
+            goal_frame: self.goal_frame,
+            rng: self.rng.clone(),
+            version: self.version,
+            base_state: self.base_state,
+            history: self.history.try_map(f)?,
+            inputs: self.inputs.clone(),
+        })
+    }
+}
+
+/// The size (in bytes) of the save state serialized the way it is stored in files,
+/// or 0 if it cannot be serialized.
+pub(crate) fn stored_size<T: Tasable>(state: &SaveState<T::Saved>) -> usize {
+    state
+        .try_map(StoredState::store::<T>)
+        .and_then(|stored| Ok(serde_json::to_vec(&stored)?.len()))
+        .unwrap_or(0)
+}
+
+/// The FNV-1a hash, used for the hashes stored in files,
+/// as (unlike the hasher of the standard library) it never changes.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl std::hash::Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hashes the game state as serialized to JSON, to find the same state elsewhere.
+/// Only stable if the state serializes deterministically (e.g. without hash maps).
+pub(crate) fn state_hash(state: &impl Serialize) -> Result<u64, Box<dyn std::error::Error>> {
+    use std::hash::Hasher;
+
+    let mut hasher = Fnv1a::default();
+    hasher.write(&serde_json::to_vec(state)?);
+    Ok(hasher.finish())
+}
+
+/// Hashes the initial state and the inputs of a run, see [Tas::run_hash].
+pub(crate) fn run_hash(
+    initial_state: &impl Serialize,
+    inputs: &[FrameInput<geng::Event>],
+) -> Result<u64, Box<dyn std::error::Error>> {
+    use std::hash::Hasher;
+
+    let mut hasher = Fnv1a::default();
+    hasher.write(&serde_json::to_vec(initial_state)?);
+    hasher.write(&serde_json::to_vec(&to_stable(inputs))?);
+    Ok(hasher.finish())
+}
+
+/// Converts the states of a run saved with a different [Tasable::saved_version].
+pub(crate) fn migrate_run<T: Tasable>(game: &T, saved: &mut SavedTas<T::Saved>) {
+    if saved.version == T::saved_version() {
+        return;
+    }
+    log_info!(
+        "Migrating a run from version {} to {}",
+        saved.version,
+        T::saved_version()
+    );
+    for state in saved.history.iter_mut() {
+        *state = game.migrate(state.clone(), saved.version);
+    }
+    saved.version = T::saved_version();
+}
+
+/// Converts the states of a save state saved with a different [Tasable::saved_version].
+pub(crate) fn migrate_save_state<T: Tasable>(game: &T, saved: &mut SaveState<T::Saved>) {
+    if saved.version == T::saved_version() {
+        return;
+    }
+    if let Some(state) = &mut saved.state {
+        *state = game.migrate(state.clone(), saved.version);
+    }
+    for state in saved.history.iter_mut() {
+        *state = game.migrate(state.clone(), saved.version);
+    }
+    saved.version = T::saved_version();
+}
+
+/// The first line of a recording log or a streamable run, followed by one input per line.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct StreamHeader {
+    /// A description of the format, missing in older logs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    header: Option<RunHeader>,
+    /// The format of the initial state, see [Tasable::saved_version].
+    version: u32,
+    initial_state: StoredState,
+}
+
+impl StreamHeader {
+    pub(crate) fn new<T: Tasable>(
+        initial_state: &T::Saved,
+        fixed_delta_time: f64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            header: Some(RunHeader::streamed(fixed_delta_time)),
+            version: T::saved_version(),
+            initial_state: StoredState::store::<T>(initial_state)?,
+        })
+    }
+
+    /// Restores the initial state, converting it if it was saved with another
+    /// [Tasable::saved_version].
+    pub(crate) fn restore<T: Tasable>(
+        &self,
+        game: &T,
+    ) -> Result<T::Saved, Box<dyn std::error::Error>> {
+        if let Some(header) = &self.header {
+            header.validate(None)?;
+        }
+        let state = self.initial_state.restore::<T>()?;
+        if self.version == T::saved_version() {
+            return Ok(state);
+        }
+        log_info!(
+            "Migrating a run from version {} to {}",
+            self.version,
+            T::saved_version()
+        );
+        Ok(game.migrate(state, self.version))
+    }
+}
+
+/// Serializes the value as JSON, indented unless `compact`, see [TasConfig::compact_json].
+pub(crate) fn to_json(value: &impl Serialize, compact: bool) -> serde_json::Result<Vec<u8>> {
+    if compact {
+        serde_json::to_vec(value)
+    } else {
+        serde_json::to_vec_pretty(value)
+    }
+}
+
+/// The version of the run file format, see [RunHeader].
+const RUN_FORMAT_VERSION: u32 = 1;
+
+/// The representation of the events in `inputs`, see [StableEvent].
+const EVENT_FORMAT: &str = "stable";
+
+/// Descriptions of the fields of a run file, written into its header.
+const RUN_FIELDS: &[(&str, &str)] = &[
+    (
+        "fixed_delta_time",
+        "The time between frames (in seconds) at the moment of recording",
+    ),
+    (
+        "framebuffer_size",
+        "The window size (in pixels) at the moment of recording",
+    ),
+    (
+        "rerecords",
+        "The number of rerecords made while recording the run",
+    ),
+    ("total_frames", "The number of frames in the run"),
+    ("input_count", "The number of entries in `inputs`"),
+    (
+        "render_hashes",
+        "Hashes of the drawn frames by frame, to detect nondeterministic rendering",
+    ),
+    (
+        "frame_intervals",
+        "The real time (in seconds) before each frame",
+    ),
+    (
+        "comments",
+        "Notes attached to frame ranges, as [{start, end}, text]",
+    ),
+    ("goal_frame", "The frame the run should end before"),
+    (
+        "rng",
+        "The state of the game's RNG at the start of the run, encoded in base64",
+    ),
+    ("version", "The version of the game's state format"),
+    (
+        "base_state",
+        "The hash of the save state the run starts from, instead of the first state",
+    ),
+    (
+        "state_interval",
+        "The number of frames between consecutive `states`",
+    ),
+    (
+        "states",
+        "Snapshots of the game state at the start of every `state_interval`-th frame, \
+        the first one is the initial state unless `base_state` is set",
+    ),
+    (
+        "extra_states",
+        "A list of [frame, state] snapshots taken outside of `state_interval`, \
+        e.g. when the game reloaded its state",
+    ),
+    ("inputs", INPUTS_DESCRIPTION),
+];
+
+/// Descriptions of the fields of a streamable run, written into its header.
+const STREAM_FIELDS: &[(&str, &str)] = &[
+    ("version", "The version of the game's state format"),
+    ("initial_state", "The game state the run starts from"),
+    ("lines", "Every following line is one entry of `inputs`"),
+    ("inputs", INPUTS_DESCRIPTION),
+];
+
+const INPUTS_DESCRIPTION: &str = "A list of {frames, inputs, timestamps, cursor, delta_time}: \
+    the events in `inputs` are applied on each of `frames` frames, \
+    optionally with the time (in seconds) since the start of the frame each event arrived at, \
+    the cursor position and the delta time of the frames";
+
+/// A self-describing preamble of a run file, so that tools (and humans)
+/// can make sense of it without the crate's source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct RunHeader {
+    /// The version of the run file format.
+    format_version: u32,
+    /// How the events are represented.
+    events: String,
+    /// The time between frames (in seconds) the run was recorded with.
+    fixed_delta_time: f64,
+    /// Descriptions of the other fields of the file.
+    #[serde(default)]
+    fields: BTreeMap<String, String>,
+}
+
+impl RunHeader {
+    pub(crate) fn new(fixed_delta_time: f64) -> Self {
+        Self::with_fields(fixed_delta_time, RUN_FIELDS)
+    }
+
+    /// The header of a streamable run, see [StreamHeader].
+    fn streamed(fixed_delta_time: f64) -> Self {
+        Self::with_fields(fixed_delta_time, STREAM_FIELDS)
+    }
+
+    fn with_fields(fixed_delta_time: f64, fields: &[(&str, &str)]) -> Self {
+        Self {
+            format_version: RUN_FORMAT_VERSION,
+            events: EVENT_FORMAT.to_owned(),
+            fixed_delta_time,
+            fields: fields
+                .iter()
+                .map(|&(field, description)| (field.to_owned(), description.to_owned()))
+                .collect(),
+        }
+    }
+
+    /// Checks that the run can be read by this version of the crate.
+    fn validate(&self, fixed_delta_time: Option<f64>) -> Result<(), Box<dyn std::error::Error>> {
+        if self.format_version > RUN_FORMAT_VERSION {
+            return Err(format!(
+                "The run has format version {}, but only versions up to {RUN_FORMAT_VERSION} \
+                are supported",
+                self.format_version
+            )
+            .into());
+        }
+        if self.events != EVENT_FORMAT {
+            return Err(format!("Unknown event format: {:?}", self.events).into());
+        }
+        if let Some(recorded) = fixed_delta_time {
+            if (recorded - self.fixed_delta_time).abs() > 1e-9 {
+                log_warn!(
+                    "The header of the run has fixed delta time {}, but the run has {recorded}, \
+                    the file may be corrupted",
+                    self.fixed_delta_time
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serializes a run, storing the states in the format chosen by the game.
+pub(crate) fn write_run<T: Tasable>(
+    saved: &SavedTas<T::Saved>,
+    compact: bool,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let stored = saved.try_map(StoredState::store::<T>)?;
+    Ok(to_json(&stored, compact)?)
+}
+
+/// Deserializes a run written with [write_run].
+pub(crate) fn read_run<T: Tasable>(
+    bytes: &[u8],
+) -> Result<SavedTas<T::Saved>, Box<dyn std::error::Error>> {
+    let stored: SavedTas<StoredState> = serde_json::from_slice(bytes)?;
+    if let Some(header) = &stored.header {
+        header.validate(stored.fixed_delta_time)?;
+    }
+    stored.verify();
+    stored.try_map(StoredState::restore::<T>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct State {
+        custom: String,
+        delta: Option<i32>,
+    }
+
+    struct Game {
+        state: State,
+    }
+
+    impl Tasable for Game {
+        type Saved = State;
+
+        fn save(&self) -> State {
+            self.state.clone()
+        }
+
+        fn load(&mut self, state: State) {
+            self.state = state;
+        }
+    }
+
+    /// A game whose states cannot always be saved as JSON, which needs string keys.
+    struct MapGame {
+        cells: BTreeMap<(i32, i32), i32>,
+    }
+
+    impl Tasable for MapGame {
+        type Saved = BTreeMap<(i32, i32), i32>;
+
+        fn save(&self) -> Self::Saved {
+            self.cells.clone()
+        }
+
+        fn load(&mut self, state: Self::Saved) {
+            self.cells = state;
+        }
+    }
+
+    fn reparse(stored: &StoredState) -> StoredState {
+        serde_json::from_str(&serde_json::to_string(stored).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn fields_named_like_variants() {
+        let state = State {
+            custom: "text".to_owned(),
+            delta: Some(1),
+        };
+        let stored = reparse(&StoredState::store::<Game>(&state).unwrap());
+        assert!(matches!(stored, StoredState::Serde(_)));
+        assert_eq!(stored.restore::<Game>().unwrap(), state);
+    }
+
+    #[test]
+    fn run_fields_described() {
+        let saved = SavedTas {
+            header: None,
+            fixed_delta_time: Some(1.0 / 60.0),
+            framebuffer_size: Some(vec2(640, 480)),
+            rerecords: 1,
+            total_frames: Some(2),
+            input_count: Some(1),
+            render_hashes: [(0, 0)].into_iter().collect(),
+            frame_intervals: vec![1.0 / 60.0],
+            comments: vec![(0..1, "comment".to_owned())],
+            goal_frame: Some(2),
+            rng: vec![0],
+            version: 0,
+            base_state: Some(0),
+            history: History {
+                interval: 1,
+                states: vec![0],
+                extra: vec![(1, 1)],
+            },
+            inputs: vec![FrameInput::new(2, Vec::new())],
+        };
+        let serde_json::Value::Object(fields) = serde_json::to_value(saved).unwrap() else {
+            panic!("A run is not serialized as an object");
+        };
+        for field in fields.keys() {
+            assert!(
+                RUN_FIELDS.iter().any(|&(described, _)| described == field),
+                "The field {field} is not described in RUN_FIELDS"
+            );
+        }
+    }
+
+    #[test]
+    fn unserializable_state_rejected() {
+        assert!(StoredState::store::<MapGame>(&BTreeMap::new()).is_ok());
+        let state = [((0, 0), 1)].into_iter().collect();
+        assert!(StoredState::store::<MapGame>(&state).is_err());
+    }
+
+    #[test]
+    fn state_hash_is_fixed() {
+        assert_eq!(state_hash(&"state").unwrap(), 0xfb7e_a874_1d01_b29c);
+    }
+
+    #[test]
+    fn run_hash_is_fixed() {
+        let press = geng::Event::KeyDown { key: geng::Key::A };
+        let inputs = [FrameInput::new(2, vec![press])];
+        assert_eq!(run_hash(&"state", &inputs).unwrap(), 0xac9d_025e_cdda_4fe0);
+    }
+
+    #[test]
+    fn delta_keeps_null_fields() {
+        let states = [
+            State {
+                custom: "a".to_owned(),
+                delta: Some(1),
+            },
+            State {
+                custom: "a".to_owned(),
+                delta: None,
+            },
+            State {
+                custom: "b".to_owned(),
+                delta: None,
+            },
+        ];
+        let mut encoder = DeltaEncoder::new(true);
+        let stored: Vec<StoredState> = states
+            .iter()
+            .map(|state| reparse(&encoder.store::<Game>(state).unwrap()))
+            .collect();
+        assert!(matches!(stored[1], StoredState::Delta { .. }));
+
+        let mut decoder = DeltaDecoder::default();
+        for (stored, state) in stored.iter().zip(&states) {
+            assert_eq!(&decoder.restore::<Game>(stored).unwrap(), state);
+        }
+    }
+
+    #[test]
+    fn delta_removes_fields() {
+        let from = serde_json::json!({ "kept": 1, "removed": null, "nested": { "a": 1, "b": 2 } });
+        let to = serde_json::json!({ "kept": 1, "nested": { "a": null }, "added": [1] });
+        let mut value = from.clone();
+        apply_json_delta(&mut value, &json_delta(&from, &to)).unwrap();
+        assert_eq!(value, to);
+    }
+}
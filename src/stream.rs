@@ -0,0 +1,103 @@
+//! Replaying runs that are too big to load at once.
+//!
+//! Only the reading is deferred: the inputs are kept once replayed,
+//! so that the replay can seek back, and memory still grows with the length of the replay.
+
+use super::*;
+
+use std::io::BufRead;
+
+/// The rest of a streamed run file, see [Tas::stream_run].
+pub(crate) type InputStream = std::io::Lines<std::io::BufReader<std::fs::File>>;
+
+impl<T> Replay<T> {
+    /// Reads the next input from the stream once all the loaded ones have been replayed.
+    pub(crate) fn fetch_input(&mut self) {
+        if self.input < self.inputs.len() {
+            return;
+        }
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        let result = match stream.next() {
+            Some(line) => line.map_err(|err| err.to_string()).and_then(|line| {
+                serde_json::from_str::<FrameInput<AnyEvent>>(&line).map_err(|err| err.to_string())
+            }),
+            None => {
+                self.stream = None;
+                return;
+            }
+        };
+        match result {
+            Ok(input) => {
+                self.total_frames += input.frames;
+                self.inputs.push(input.filter_map(|event| event.to_event()));
+            }
+            Err(err) => {
+                log_error!("Failed to read the streamed run: {err}");
+                self.stream = None;
+            }
+        }
+    }
+}
+
+/// Explains why the file cannot be streamed, telling apart the runs saved the normal way.
+fn not_streamable(path: &std::path::Path, err: serde_json::Error) -> Box<dyn std::error::Error> {
+    let run = std::fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<SavedTas<serde_json::Value>>(&bytes).ok());
+    match run {
+        Some(run) if run.base_state.is_some() => "The run starts from a save state, \
+            which streamed runs do not support, load it with `load_run` instead"
+            .into(),
+        Some(_) => "The run is not saved for streaming, load it with `load_run` instead".into(),
+        None => format!("Failed to read the streamed run: {err}").into(),
+    }
+}
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Saves the run with one input per line, so that it can be replayed with [Self::stream_run].
+    /// This is the same format as [TasConfig::recording_log].
+    pub fn save_run_streamable(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.rewrite_log(path)
+    }
+
+    /// Replays the run saved with [Self::save_run_streamable], reading its inputs
+    /// from the file as the replay reaches them, so that even huge runs start immediately.
+    /// Only the initial state is known in advance, so seeking is limited
+    /// to the frames replayed so far and takes longer the further it goes.
+    /// The replayed inputs and snapshots stay in memory, so it grows with the replay as usual.
+    pub fn stream_run(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)?;
+        let mut lines = std::io::BufReader::new(file).lines();
+        let header = lines.next().ok_or("The run file is empty")??;
+        let header: StreamHeader = match serde_json::from_str(&header) {
+            Ok(header) => header,
+            Err(err) => return Err(not_streamable(path, err)),
+        };
+        let initial_state = header.restore(&self.game)?;
+
+        if !self.config.keep_states_on_load {
+            self.clear_states_for_new_run();
+        }
+        self.snapshot_before_load();
+        self.game.load(initial_state);
+        self.pressed_keys.clear();
+        self.pressed_buttons.clear();
+        self.looped_range = None;
+        self.replay_inputs_from_current(Vec::new());
+        if let Some(replay) = &mut self.replay {
+            replay.stream = Some(lines);
+            replay.fetch_input();
+            replay.next_input = replay.inputs.first().map_or(0, |input| input.frames);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use super::*;
+
+/// How long (in seconds) a notification stays on screen.
+const TOAST_DURATION: f64 = 2.0;
+/// How long (in seconds) a notification takes to fade out at the end.
+const TOAST_FADE: f64 = 0.5;
+/// The maximum number of notifications shown at once.
+const MAX_TOASTS: usize = 5;
+
+/// A short message shown on screen for a limited time.
+pub(crate) struct Toast {
+    pub text: String,
+    /// Time (in seconds) until the toast disappears.
+    pub time_left: f64,
+}
+
+impl Toast {
+    /// The opacity of the toast, going to zero as it disappears.
+    pub fn alpha(&self) -> f32 {
+        (self.time_left / TOAST_FADE).clamp(0.0, 1.0) as f32
+    }
+}
+
+impl<T: geng::State + Tasable> Tas<T> {
+    /// Shows a short message on screen, if notifications are enabled.
+    pub fn notify(&mut self, text: impl Into<String>) {
+        if !self.config.show_notifications {
+            return;
+        }
+        if self.toasts.len() >= MAX_TOASTS {
+            self.toasts.pop_front();
+        }
+        self.toasts.push_back(Toast {
+            text: text.into(),
+            time_left: TOAST_DURATION,
+        });
+    }
+
+    /// Logs the error and shows it on screen.
+    pub(crate) fn notify_error(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        log_error!("{text}");
+        self.notify(text);
+    }
+
+    pub(crate) fn update_toasts(&mut self, delta_time: f64) {
+        for toast in &mut self.toasts {
+            toast.time_left -= delta_time;
+        }
+        self.toasts.retain(|toast| toast.time_left > 0.0);
+    }
+}
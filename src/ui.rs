@@ -164,3 +164,336 @@ impl<'a> Widget for Slider<'a> {
         }
     }
 }
+
+/// A horizontal bar spanning the whole run, with markers and a playhead.
+/// Clicking or dragging on it selects a frame.
+pub struct Timeline<'a> {
+    cx: &'a Controller,
+    sense: &'a mut Sense,
+    pos: &'a mut Option<Aabb2<f64>>,
+    frame: usize,
+    length: usize,
+    markers: Vec<usize>,
+    change: RefCell<&'a mut Option<usize>>,
+    hover: &'a mut Option<usize>,
+}
+
+impl<'a> Timeline<'a> {
+    pub fn new(cx: &'a Controller, frame: usize, length: usize, markers: Vec<usize>) -> Self {
+        Timeline {
+            cx,
+            sense: cx.get_state(),
+            pos: cx.get_state(),
+            frame,
+            length: length.max(1),
+            markers,
+            change: RefCell::new(cx.get_state()),
+            hover: cx.get_state(),
+        }
+    }
+
+    pub fn get_change(&self) -> Option<usize> {
+        self.change.borrow_mut().take()
+    }
+
+    /// The frame under the cursor, if it is over the timeline.
+    pub fn get_hover(&self) -> Option<usize> {
+        *self.hover
+    }
+
+    fn frame_at(&self, aabb: Aabb2<f64>, x: f64) -> usize {
+        let t = ((x - aabb.min.x) / aabb.width()).clamp(0.0, 1.0);
+        (t * self.length as f64).round() as usize
+    }
+
+    fn frame_position(&self, position: Aabb2<f32>, frame: usize) -> f32 {
+        position.min.x + position.width() * (frame.min(self.length) as f32 / self.length as f32)
+    }
+}
+
+impl<'a> Widget for Timeline<'a> {
+    fn sense(&mut self) -> Option<&mut Sense> {
+        Some(self.sense)
+    }
+    fn draw(&mut self, cx: &mut DrawContext) {
+        *self.pos = Some(cx.position);
+        let draw2d = cx.draw2d;
+        let position = cx.position.map(|x| x as f32);
+        let line_width = position.height() / 3.0;
+
+        // Bar
+        draw2d.draw2d(
+            cx.framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw2d::Quad::new(
+                Aabb2::from_corners(
+                    position.bottom_left() + vec2(0.0, (position.height() - line_width) / 2.0),
+                    position.top_right() - vec2(0.0, (position.height() - line_width) / 2.0),
+                ),
+                cx.theme.usable_color,
+            ),
+        );
+
+        // Markers
+        for &marker in &self.markers {
+            let x = self.frame_position(position, marker);
+            draw2d.draw2d(
+                cx.framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw2d::Quad::new(
+                    Aabb2::from_corners(
+                        vec2(x - line_width / 4.0, position.min.y),
+                        vec2(x + line_width / 4.0, position.max.y),
+                    ),
+                    cx.theme.hover_color,
+                ),
+            );
+        }
+
+        // Playhead
+        let x = self.frame_position(position, self.frame);
+        draw2d.draw2d(
+            cx.framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw2d::Ellipse::circle(
+                vec2(x, position.center().y),
+                position.height() / 2.0,
+                Rgba::WHITE,
+            ),
+        );
+    }
+    fn handle_event(&mut self, event: &geng::Event) {
+        let aabb = match *self.pos {
+            Some(pos) => pos,
+            None => return,
+        };
+        if let geng::Event::MouseMove { position, .. } = &event {
+            *self.hover = aabb
+                .contains(*position)
+                .then(|| self.frame_at(aabb, position.x));
+        }
+        if self.sense.is_captured() {
+            if let geng::Event::MouseDown { position, .. }
+            | geng::Event::MouseMove { position, .. } = &event
+            {
+                let frame = self.frame_at(aabb, position.x);
+                if frame != self.frame {
+                    **self.change.borrow_mut() = Some(frame);
+                }
+            }
+        }
+    }
+
+    fn calc_constraints(&mut self, _children: &ConstraintsContext) -> Constraints {
+        Constraints {
+            min_size: vec2(1.0, 1.0) * self.cx.theme().text_size as f64,
+            flex: vec2(1.0, 0.0),
+        }
+    }
+}
+
+/// The result of interacting with a text field.
+pub struct TextInputResponse {
+    pub focused: bool,
+    /// The edited text, if it has changed.
+    pub change: Option<String>,
+    /// The text, if it has been submitted with Enter.
+    pub submit: Option<String>,
+}
+
+/// A single-line text field showing the `value` while it is not being edited.
+pub fn text_input<'a>(
+    cx: &'a Controller,
+    value: &str,
+    font: Rc<geng::Font>,
+    text_size: f32,
+) -> (impl geng::ui::Widget + 'a, TextInputResponse) {
+    let input = TextInput::new(cx, value);
+    let response = TextInputResponse {
+        focused: input.is_focused(),
+        change: input.get_change(),
+        submit: input.get_submit(),
+    };
+    let focused = response.focused;
+
+    let mut display = input.text().to_owned();
+    if focused {
+        display.push('|');
+    }
+    let widget = geng::ui::stack![
+        input,
+        geng::ui::Text::new(display, font, text_size, Rgba::WHITE),
+    ]
+    .fixed_size(vec2(text_size * 10.0, text_size).map(f64::from));
+
+    (widget, response)
+}
+
+pub struct TextInput<'a> {
+    sense: &'a mut Sense,
+    pos: &'a mut Option<Aabb2<f64>>,
+    focused: &'a mut bool,
+    buffer: &'a mut String,
+    change: RefCell<&'a mut Option<String>>,
+    submit: RefCell<&'a mut Option<String>>,
+}
+
+impl<'a> TextInput<'a> {
+    pub fn new(cx: &'a Controller, value: &str) -> Self {
+        let input = TextInput {
+            sense: cx.get_state(),
+            pos: cx.get_state(),
+            focused: cx.get_state(),
+            buffer: cx.get_state(),
+            change: RefCell::new(cx.get_state()),
+            submit: RefCell::new(cx.get_state()),
+        };
+        if !*input.focused && input.buffer != value {
+            // Follow the external value while not being edited
+            *input.buffer = value.to_owned();
+        }
+        input
+    }
+
+    pub fn text(&self) -> &str {
+        self.buffer
+    }
+
+    pub fn is_focused(&self) -> bool {
+        *self.focused
+    }
+
+    pub fn get_change(&self) -> Option<String> {
+        self.change.borrow_mut().take()
+    }
+
+    pub fn get_submit(&self) -> Option<String> {
+        self.submit.borrow_mut().take()
+    }
+}
+
+/// Converts the key into the character it types, if any.
+fn key_char(key: geng::Key) -> Option<char> {
+    let name = format!("{key:?}");
+    match name.as_str() {
+        "Space" => Some(' '),
+        "Period" => Some('.'),
+        "Comma" => Some(','),
+        "Minus" => Some('-'),
+        "Slash" => Some('/'),
+        _ => {
+            let name = name.strip_prefix("Num").unwrap_or(&name);
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_alphanumeric() => Some(c.to_ascii_lowercase()),
+                _ => None,
+            }
+        }
+    }
+}
+
+impl<'a> Widget for TextInput<'a> {
+    fn sense(&mut self) -> Option<&mut Sense> {
+        Some(self.sense)
+    }
+    fn draw(&mut self, cx: &mut DrawContext) {
+        *self.pos = Some(cx.position);
+        let position = cx.position.map(|x| x as f32);
+        let color = if *self.focused {
+            cx.theme.hover_color
+        } else {
+            cx.theme.usable_color
+        };
+        let line_width = position.height() / 12.0;
+        cx.draw2d.draw2d(
+            cx.framebuffer,
+            &geng::PixelPerfectCamera,
+            &draw2d::Quad::new(
+                Aabb2::from_corners(
+                    position.bottom_left(),
+                    position.bottom_right() + vec2(0.0, line_width),
+                ),
+                color,
+            ),
+        );
+    }
+    fn handle_event(&mut self, event: &geng::Event) {
+        match event {
+            geng::Event::MouseDown { position, .. } => {
+                if let Some(aabb) = *self.pos {
+                    *self.focused = aabb.contains(*position);
+                }
+            }
+            geng::Event::KeyDown { key } if *self.focused => {
+                match key {
+                    geng::Key::Enter => {
+                        *self.focused = false;
+                        **self.submit.borrow_mut() = Some(self.buffer.clone());
+                        return;
+                    }
+                    geng::Key::Escape => {
+                        *self.focused = false;
+                        return;
+                    }
+                    geng::Key::Backspace => {
+                        self.buffer.pop();
+                    }
+                    _ => match key_char(*key) {
+                        Some(c) => self.buffer.push(c),
+                        None => return,
+                    },
+                }
+                **self.change.borrow_mut() = Some(self.buffer.clone());
+            }
+            _ => {}
+        }
+    }
+
+    fn calc_constraints(&mut self, _children: &ConstraintsContext) -> Constraints {
+        Constraints {
+            min_size: vec2(1.0, 1.0),
+            flex: vec2(1.0, 0.0),
+        }
+    }
+}
+
+/// A simple bar graph of values in range `0..=1`.
+pub struct BarGraph<'a> {
+    cx: &'a Controller,
+    values: Vec<f64>,
+}
+
+impl<'a> BarGraph<'a> {
+    pub fn new(cx: &'a Controller, values: Vec<f64>) -> Self {
+        Self { cx, values }
+    }
+}
+
+impl<'a> Widget for BarGraph<'a> {
+    fn draw(&mut self, cx: &mut DrawContext) {
+        let position = cx.position.map(|x| x as f32);
+        let width = position.width() / self.values.len().max(1) as f32;
+        for (i, &value) in self.values.iter().enumerate() {
+            let x = position.min.x + width * i as f32;
+            let height = position.height() * value.clamp(0.0, 1.0) as f32;
+            cx.draw2d.draw2d(
+                cx.framebuffer,
+                &geng::PixelPerfectCamera,
+                &draw2d::Quad::new(
+                    Aabb2::from_corners(
+                        vec2(x, position.min.y),
+                        vec2(x + width, position.min.y + height),
+                    ),
+                    cx.theme.usable_color,
+                ),
+            );
+        }
+    }
+
+    fn calc_constraints(&mut self, _children: &ConstraintsContext) -> Constraints {
+        Constraints {
+            min_size: vec2(1.0, 1.0) * self.cx.theme().text_size as f64,
+            flex: vec2(1.0, 0.0),
+        }
+    }
+}